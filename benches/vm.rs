@@ -0,0 +1,43 @@
+//! Compares the tree walker with the bytecode VM on a loop-heavy script.
+//!
+//!     cargo bench --bench vm
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use bingbang::bytecode::Chunk;
+use bingbang::{parse_bng, Interpreter};
+
+const SCRIPT: &str = "200000 (1 2 + 3 * 4 - d 5 5 = ? [6 7 < ! d] 3 (2 2 * d))";
+const RUNS: u32 = 10;
+
+fn time(mut f: impl FnMut()) -> Duration {
+    // warm up once, then take the fastest run
+    f();
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let instructions = parse_bng(SCRIPT.into()).unwrap();
+    let chunk = Chunk::compile(&instructions);
+
+    let tree = time(|| {
+        let mut interpreter = Interpreter::with_io(io::empty(), io::sink());
+        interpreter.execute(&instructions).unwrap();
+    });
+    let vm = time(|| {
+        let mut interpreter = Interpreter::with_io(io::empty(), io::sink());
+        interpreter.execute_chunk(&chunk).unwrap();
+    });
+
+    println!("tree walker: {tree:>10.2?}");
+    println!("bytecode vm: {vm:>10.2?}");
+    println!("speedup:     {:>9.2}x", tree.as_secs_f64() / vm.as_secs_f64());
+}
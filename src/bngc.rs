@@ -0,0 +1,324 @@
+//! The `.bngc` binary format for parsed programs, so they can be shipped and
+//! run without their source.
+//!
+//! ```text
+//! magic     b"BNGC"
+//! version   u16
+//! length    u32       length of the payload in bytes
+//! checksum  u32       crc32 of the payload
+//! payload   body
+//!
+//! body      u32 count, followed by count instructions
+//! instr     u8 tag, then for
+//!             PushNum         f32
+//!             PushBool        u8, 0 or 1
+//!             PushStr/FnCall  u32 length, utf-8 bytes
+//!             IfStmt/Loop     body
+//! ```
+//!
+//! All integers and floats are little endian.
+//!
+//! Tags are only ever added, and a reader rejects ones it doesn't know.
+//! Version 2 added tags 32 to 40, `PushBool` and the instructions in
+//! [`crate::time`]; version 1 files are still read.
+
+use crate::parse::MAX_DEPTH;
+use crate::*;
+
+pub const MAGIC: &[u8; 4] = b"BNGC";
+pub const VERSION: u16 = 2;
+
+const HEADER_LEN: usize = 4 + 2 + 4 + 4;
+
+/// Returns whether `bytes` look like a `.bngc` file rather than source.
+pub fn is_bngc(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn encode(instructions: &[Instr]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    encode_body(instructions, &mut payload);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&crc32(&payload).to_le_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<Instr>, BngError> {
+    let err = |msg: &str| BngError::Parse(format!("invalid bngc file: {msg}."));
+
+    if bytes.len() < HEADER_LEN || !is_bngc(bytes) {
+        return Err(err("missing header"));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if !(1..=VERSION).contains(&version) {
+        return Err(err(&format!(
+            "version {version} is not supported (expected 1 to {VERSION})"
+        )));
+    }
+    let len = u32::from_le_bytes(bytes[6..10].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(bytes[10..14].try_into().unwrap());
+    let payload = &bytes[HEADER_LEN..];
+
+    if payload.len() != len {
+        return Err(err("truncated payload"));
+    }
+    if crc32(payload) != checksum {
+        return Err(err("checksum mismatch"));
+    }
+
+    let mut reader = Reader {
+        bytes: payload,
+        pos: 0,
+        depth: 0,
+    };
+    let instructions = reader.body().map_err(|e| err(&e))?;
+    if reader.pos != payload.len() {
+        return Err(err("trailing bytes after program"));
+    }
+    Ok(instructions)
+}
+
+fn encode_body(instructions: &[Instr], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(instructions.len() as u32).to_le_bytes());
+
+    for inst in instructions {
+        out.push(tag(inst));
+        match inst {
+            Instr::PushNum(n) => out.extend_from_slice(&n.to_le_bytes()),
+            Instr::PushBool(b) => out.push(*b as u8),
+            Instr::PushStr(s) | Instr::FnCall(s) => {
+                out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                out.extend_from_slice(s.as_bytes());
+            }
+            Instr::IfStmt(body) | Instr::Loop(body) => encode_body(body, out),
+            _ => {}
+        }
+    }
+}
+
+/// Tags are part of the file format; never renumber them.
+fn tag(inst: &Instr) -> u8 {
+    match inst {
+        Instr::Print => 0,
+        Instr::PrintLn => 1,
+        Instr::Read => 2,
+        Instr::Div => 3,
+        Instr::Plus => 4,
+        Instr::Sub => 5,
+        Instr::Mul => 6,
+        Instr::GreaterThan => 7,
+        Instr::LessThan => 8,
+        Instr::Sum => 9,
+        Instr::ParseNum => 10,
+        Instr::Reverse => 11,
+        Instr::GenRange => 12,
+        Instr::FlipStack => 13,
+        Instr::FnDef => 14,
+        Instr::FnCall(_) => 15,
+        Instr::Loop(_) => 16,
+        Instr::Pop => 17,
+        Instr::ClearScreen => 18,
+        Instr::PushStr(_) => 19,
+        Instr::PushNum(_) => 20,
+        Instr::Syscall => 21,
+        Instr::Time => 22,
+        Instr::TimeFmt => 23,
+        Instr::IfStmt(_) => 24,
+        Instr::Eq => 25,
+        Instr::Not => 26,
+        Instr::ClearStack => 27,
+        Instr::PrintStack => 28,
+        Instr::PrintStackLn => 29,
+        Instr::ShowStack => 30,
+        Instr::Exit => 31,
+        Instr::PushBool(_) => 32,
+        Instr::Now => 33,
+        Instr::Epoch => 34,
+        Instr::EpochMillis => 35,
+        Instr::ParseTime => 36,
+        Instr::FormatTime => 37,
+        Instr::AddTime => 38,
+        Instr::TimeDiff => 39,
+        Instr::InZone => 40,
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Bodies entered and not yet left.
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.bytes.len() - self.pos < n {
+            return Err("unexpected end of payload".into());
+        }
+        let bytes = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "string is not utf-8".into())
+    }
+
+    fn body(&mut self) -> Result<Vec<Instr>, String> {
+        if self.depth > MAX_DEPTH {
+            return Err(format!("bodies are nested more than {MAX_DEPTH} deep"));
+        }
+        self.depth += 1;
+        let count = self.u32()?;
+        // every instruction takes at least one byte
+        if count as usize > self.bytes.len() - self.pos {
+            return Err("unexpected end of payload".into());
+        }
+
+        let mut instructions = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let inst = match self.take(1)?[0] {
+                0 => Instr::Print,
+                1 => Instr::PrintLn,
+                2 => Instr::Read,
+                3 => Instr::Div,
+                4 => Instr::Plus,
+                5 => Instr::Sub,
+                6 => Instr::Mul,
+                7 => Instr::GreaterThan,
+                8 => Instr::LessThan,
+                9 => Instr::Sum,
+                10 => Instr::ParseNum,
+                11 => Instr::Reverse,
+                12 => Instr::GenRange,
+                13 => Instr::FlipStack,
+                14 => Instr::FnDef,
+                15 => Instr::FnCall(self.string()?),
+                16 => Instr::Loop(self.body()?),
+                17 => Instr::Pop,
+                18 => Instr::ClearScreen,
+                19 => Instr::PushStr(self.string()?),
+                20 => Instr::PushNum(f32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+                21 => Instr::Syscall,
+                22 => Instr::Time,
+                23 => Instr::TimeFmt,
+                24 => Instr::IfStmt(self.body()?),
+                25 => Instr::Eq,
+                26 => Instr::Not,
+                27 => Instr::ClearStack,
+                28 => Instr::PrintStack,
+                29 => Instr::PrintStackLn,
+                30 => Instr::ShowStack,
+                31 => Instr::Exit,
+                32 => match self.take(1)?[0] {
+                    0 => Instr::PushBool(false),
+                    1 => Instr::PushBool(true),
+                    b => return Err(format!("invalid bool {b}")),
+                },
+                33 => Instr::Now,
+                34 => Instr::Epoch,
+                35 => Instr::EpochMillis,
+                36 => Instr::ParseTime,
+                37 => Instr::FormatTime,
+                38 => Instr::AddTime,
+                39 => Instr::TimeDiff,
+                40 => Instr::InZone,
+                t => return Err(format!("unknown instruction tag {t}")),
+            };
+            instructions.push(inst);
+        }
+        self.depth -= 1;
+        Ok(instructions)
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let instructions = parse_bng(
+            "'hi'P 1.5 2 + :host 3 (1 1 = ? ['nested'P 2 (d)]) t T q w e E W F D G z".into(),
+        )
+        .unwrap();
+        let bytes = encode(&instructions);
+        assert!(is_bngc(&bytes));
+        assert_eq!(decode(&bytes).unwrap(), instructions);
+
+        let folded = vec![Instr::PushBool(true), Instr::PushBool(false)];
+        assert_eq!(decode(&encode(&folded)).unwrap(), folded);
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let nest = |depth| (0..depth).fold(vec![Instr::Pop], |body, _| vec![Instr::Loop(body)]);
+        assert_eq!(decode(&encode(&nest(MAX_DEPTH))).unwrap(), nest(MAX_DEPTH));
+
+        let deep = encode(&nest(MAX_DEPTH + 1));
+        assert_eq!(
+            decode(&deep),
+            Err(BngError::Parse(
+                "invalid bngc file: bodies are nested more than 1000 deep.".into()
+            ))
+        );
+
+        // written by hand, since encoding this many levels would overflow too
+        let mut payload = Vec::new();
+        for _ in 0..1_000_000 {
+            payload.extend_from_slice(&1u32.to_le_bytes());
+            payload.push(tag(&Instr::Loop(Vec::new())));
+        }
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        let mut bytes = encode(&[]);
+        bytes.truncate(HEADER_LEN);
+        bytes[6..10].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes[10..14].copy_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        assert!(decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_damage() {
+        let mut bytes = encode(&[Instr::PushStr("abc".into()), Instr::PrintLn]);
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(decode(&bytes).is_err());
+
+        let mut future = encode(&[]);
+        future[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            decode(&future),
+            Err(BngError::Parse(
+                "invalid bngc file: version 3 is not supported (expected 1 to 2).".into()
+            ))
+        );
+        let mut old = encode(&[Instr::PushNum(1.0)]);
+        old[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(decode(&old), Ok(vec![Instr::PushNum(1.0)]));
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,113 @@
+//! A flat form of [`Instr`] for the [`vm`](crate::vm). Bodies of ifs and
+//! loops are inlined and reached through jumps, and strings live in a
+//! constant pool.
+
+use std::collections::HashMap;
+
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    PushNum(f32),
+    /// Push the string at this index of [`Chunk::strings`].
+    PushStr(u32),
+
+    Plus,
+    Sub,
+    Mul,
+    Div,
+    GreaterThan,
+    LessThan,
+    Eq,
+    Not,
+    Pop,
+
+    /// Pop a Bool and jump to the target, just past the body's [`Op::IfEnd`],
+    /// unless it is true.
+    JumpUnless(u32),
+    /// End of an if body that was entered.
+    IfEnd,
+    /// Pop the iteration count of a loop and jump to the target, just past
+    /// the loop, if there is nothing to do.
+    LoopStart(u32),
+    /// End of a loop body: jump back to the target while iterations are left.
+    LoopNext(u32),
+
+    /// Any other instruction, performed like the tree walker does.
+    Instr(Instr),
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Chunk {
+    pub ops: Vec<Op>,
+    pub strings: Vec<String>,
+}
+
+impl Chunk {
+    pub fn compile(instructions: &[Instr]) -> Chunk {
+        let mut compiler = Compiler::default();
+        compiler.compile(instructions);
+        compiler.chunk
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    chunk: Chunk,
+    string_ids: HashMap<String, u32>,
+}
+
+impl Compiler {
+    fn compile(&mut self, instructions: &[Instr]) {
+        for inst in instructions {
+            let op = match inst {
+                Instr::PushNum(n) => Op::PushNum(*n),
+                Instr::PushStr(s) => Op::PushStr(self.string(s)),
+                Instr::Plus => Op::Plus,
+                Instr::Sub => Op::Sub,
+                Instr::Mul => Op::Mul,
+                Instr::Div => Op::Div,
+                Instr::GreaterThan => Op::GreaterThan,
+                Instr::LessThan => Op::LessThan,
+                Instr::Eq => Op::Eq,
+                Instr::Not => Op::Not,
+                Instr::Pop => Op::Pop,
+                Instr::IfStmt(body) => {
+                    let jump = self.emit(Op::JumpUnless(0));
+                    self.compile(body);
+                    self.emit(Op::IfEnd);
+                    self.chunk.ops[jump] = Op::JumpUnless(self.here());
+                    continue;
+                }
+                Instr::Loop(body) => {
+                    let start = self.emit(Op::LoopStart(0));
+                    self.compile(body);
+                    self.emit(Op::LoopNext(start as u32 + 1));
+                    self.chunk.ops[start] = Op::LoopStart(self.here());
+                    continue;
+                }
+                _ => Op::Instr(inst.clone()),
+            };
+            self.emit(op);
+        }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.ops.push(op);
+        self.chunk.ops.len() - 1
+    }
+
+    fn here(&self) -> u32 {
+        self.chunk.ops.len() as u32
+    }
+
+    fn string(&mut self, s: &str) -> u32 {
+        if let Some(id) = self.string_ids.get(s) {
+            return *id;
+        }
+        let id = self.chunk.strings.len() as u32;
+        self.chunk.strings.push(s.to_string());
+        self.string_ids.insert(s.to_string(), id);
+        id
+    }
+}
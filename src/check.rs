@@ -0,0 +1,571 @@
+//! Finds errors without running the program, by tracking what could be on
+//! the stack at each instruction: how many values at least and, where it's
+//! known, their types. Only definite problems are reported, ones that fail
+//! whenever the instruction is reached, like `+` on a string or `P` on an
+//! empty stack. Instructions that work but are likely mistakes, like `n` on
+//! a number, get a warning.
+//!
+//! ```
+//! use bingbang::check::check;
+//! use bingbang::{parse_spanned, ParseOptions};
+//!
+//! let (instructions, map) = parse_spanned("1 'a' +".into(), &ParseOptions::default()).unwrap();
+//! let problems = check(&instructions, &map);
+//! assert_eq!(problems[0].message, "[Plus] expects a Number, but got a String.");
+//! ```
+
+use std::fmt;
+
+use crate::parse::{SourceMap, Span};
+use crate::*;
+
+/// What is known about a value on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Number,
+    String,
+    /// A String read from input, whose contents are unknown.
+    Input,
+    Bool,
+    Any,
+}
+
+impl Kind {
+    fn join(self, other: Kind) -> Kind {
+        match (self, other) {
+            _ if self == other => self,
+            (Kind::String, Kind::Input) | (Kind::Input, Kind::String) => Kind::String,
+            _ => Kind::Any,
+        }
+    }
+
+    /// Whether every value of this kind is also `other`.
+    fn is(self, other: Kind) -> bool {
+        self.join(other) == other
+    }
+
+    /// How the kind is written in the dump.
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Number => "Number",
+            Kind::String => "String",
+            Kind::Input => "Input",
+            Kind::Bool => "Bool",
+            Kind::Any => "Any",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Number => write!(f, "a Number"),
+            Kind::String | Kind::Input => write!(f, "a String"),
+            Kind::Bool => write!(f, "a Bool"),
+            Kind::Any => write!(f, "any value"),
+        }
+    }
+}
+
+/// The stack at some point of the program: `known` are the values on top,
+/// bottom first, and if `more` is set there may be others below them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub known: Vec<Kind>,
+    pub more: bool,
+}
+
+impl State {
+    /// The stack a program starts with.
+    pub fn empty() -> State {
+        State {
+            known: Vec::new(),
+            more: false,
+        }
+    }
+
+    /// A stack nothing is known about.
+    pub fn unknown() -> State {
+        State {
+            known: Vec::new(),
+            more: true,
+        }
+    }
+
+    /// The values either `self` or `other` could be, lined up from the top.
+    fn join(&self, other: &State) -> State {
+        let len = self.known.len().min(other.known.len());
+        let top = |s: &State| s.known[s.known.len() - len..].to_vec();
+        let known = top(self)
+            .into_iter()
+            .zip(top(other))
+            .map(|(a, b)| a.join(b))
+            .collect();
+        State {
+            known,
+            more: self.more || other.more || self.known.len() != other.known.len(),
+        }
+    }
+}
+
+/// Written like `[Number, String]`, bottom first, with `..` in front if
+/// there may be more values.
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut kinds: Vec<&str> = self.known.iter().map(|k| k.name()).collect();
+        if self.more {
+            kinds.insert(0, "..");
+        }
+        write!(f, "[{}]", kinds.join(", "))
+    }
+}
+
+/// The stack before each instruction, shaped like the instructions the same
+/// way a [`SourceMap`] is. `None` where the instruction is never reached.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Types(pub Vec<(Option<State>, Types)>);
+
+impl Types {
+    /// Lists `instructions` with the stack before each of them, for `dump`.
+    pub fn annotate(&self, instructions: &[Instr]) -> String {
+        let mut out = String::new();
+        self.annotate_into(instructions, 0, &mut out);
+        out
+    }
+
+    fn annotate_into(&self, instructions: &[Instr], depth: usize, out: &mut String) {
+        // bodies that are never entered have no entries at all
+        let never = (None, Types::default());
+        for (idx, inst) in instructions.iter().enumerate() {
+            let (state, body) = self.0.get(idx).unwrap_or(&never);
+            let inst_str = match inst {
+                Instr::IfStmt(_) | Instr::Loop(_) => inst.name().to_string(),
+                _ => format!("{inst:?}"),
+            };
+            let state = state.as_ref().map_or("unreachable".into(), |s| s.to_string());
+            let line = format!("{}{inst_str}", "    ".repeat(depth));
+            out.push_str(&format!("{line:<32} ; {state}\n"));
+
+            if let Instr::IfStmt(insts) | Instr::Loop(insts) = inst {
+                body.annotate_into(insts, depth + 1, out);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Fails whenever it's reached.
+    Error,
+    /// Works, but likely isn't what was meant.
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Missing when the program has no source, like a `.bngc` file.
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+/// Checks `instructions`, with `map` from [`parse_spanned`], and returns the
+/// problems found in source order.
+pub fn check(instructions: &[Instr], map: &SourceMap) -> Vec<Diagnostic> {
+    infer(instructions, map).0
+}
+
+/// Like [`check`], but also returns the stack inferred before each
+/// instruction.
+pub fn infer(instructions: &[Instr], map: &SourceMap) -> (Vec<Diagnostic>, Types) {
+    let mut checker = Checker::default();
+    let mut types = Types::default();
+    checker.body(instructions, map, &mut types, State::empty());
+    checker.diagnostics.sort_by_key(|d| d.span.map(|s| s.start));
+    (checker.diagnostics, types)
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Returned on a definite error, past which the stack is unknown.
+struct Stop;
+
+impl Checker {
+    /// Returns the state after `instructions`, or `None` if the end can't be
+    /// reached.
+    fn body(
+        &mut self,
+        instructions: &[Instr],
+        map: &SourceMap,
+        types: &mut Types,
+        mut state: State,
+    ) -> Option<State> {
+        types.0.resize_with(instructions.len(), Default::default);
+
+        for (idx, inst) in instructions.iter().enumerate() {
+            // loop bodies are visited more than once, keep what holds for all
+            let (seen, body_types) = &mut types.0[idx];
+            *seen = Some(match seen {
+                Some(seen) => seen.join(&state),
+                None => state.clone(),
+            });
+
+            match self.step(inst, map, body_types, idx, &mut state) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                // carry on from a stack nothing is known about, which only
+                // finds problems that don't depend on the first one
+                Err(Stop) => state = State::unknown(),
+            }
+        }
+        Some(state)
+    }
+
+    /// Applies `inst` to `state`. Returns whether execution can continue
+    /// after it.
+    fn step(
+        &mut self,
+        inst: &Instr,
+        map: &SourceMap,
+        types: &mut Types,
+        idx: usize,
+        state: &mut State,
+    ) -> Result<bool, Stop> {
+        let span = map.span(idx);
+
+        match inst {
+            Instr::PushNum(_) => state.known.push(Kind::Number),
+            Instr::PushStr(_) | Instr::Time | Instr::Now | Instr::Epoch | Instr::EpochMillis => {
+                state.known.push(Kind::String)
+            }
+            Instr::Read => state.known.push(Kind::Input),
+            Instr::PushBool(_) => state.known.push(Kind::Bool),
+
+            Instr::Print | Instr::PrintLn => {
+                self.pop(inst, span, state, Kind::Any)?;
+            }
+            Instr::Plus | Instr::Sub | Instr::Mul | Instr::Div => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                state.known.push(Kind::Number);
+            }
+            Instr::GreaterThan | Instr::LessThan => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                state.known.push(Kind::Bool);
+            }
+            Instr::Eq => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::Any)?;
+                self.pop(inst, span, state, Kind::Any)?;
+                state.known.push(Kind::Bool);
+            }
+            Instr::Not => {
+                self.pop(inst, span, state, Kind::Bool)?;
+                state.known.push(Kind::Bool);
+            }
+            Instr::ParseNum => {
+                let got = self.pop(inst, span, state, Kind::Any)?;
+                if matches!(got, Kind::Number | Kind::Bool) {
+                    self.warn(inst, span, format!("got {got}, which is already numeric"));
+                }
+                state.known.push(Kind::Number);
+            }
+            Instr::Reverse => {
+                self.pop(inst, span, state, Kind::String)?;
+                state.known.push(Kind::String);
+            }
+            Instr::TimeFmt => {
+                self.pop(inst, span, state, Kind::String)?;
+                state.known.push(Kind::String);
+            }
+            Instr::ParseTime | Instr::FormatTime | Instr::InZone => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::String)?;
+                self.pop(inst, span, state, Kind::String)?;
+                state.known.push(Kind::String);
+            }
+            Instr::AddTime => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                self.pop(inst, span, state, Kind::String)?;
+                state.known.push(Kind::String);
+            }
+            Instr::TimeDiff => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::String)?;
+                self.pop(inst, span, state, Kind::String)?;
+                state.known.push(Kind::Number);
+            }
+            Instr::GenRange => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                // any number of values
+                *state = State {
+                    known: Vec::new(),
+                    more: true,
+                };
+            }
+            Instr::Sum => {
+                self.need(inst, span, state, 1)?;
+                // non-numbers are reported by the interpreter, but don't stop it
+                if let Some(kind) = state
+                    .known
+                    .iter()
+                    .find(|k| !matches!(k, Kind::Number | Kind::Any))
+                {
+                    self.warn(
+                        inst,
+                        span,
+                        format!("expects only Numbers on the stack, but there is {kind}"),
+                    );
+                }
+                *state = State {
+                    known: vec![Kind::Number],
+                    more: false,
+                };
+            }
+            Instr::Pop => {
+                state.known.pop();
+            }
+            Instr::FlipStack => {
+                if state.more {
+                    state.known.fill(Kind::Any);
+                } else {
+                    state.known.reverse();
+                }
+            }
+            Instr::ClearStack | Instr::PrintStackLn => *state = State::empty(),
+            Instr::PrintStack => {
+                // pops all but the bottom value
+                *state = match (state.known.first(), state.more) {
+                    (Some(bottom), false) => State {
+                        known: vec![*bottom],
+                        more: false,
+                    },
+                    (None, false) => State::empty(),
+                    (_, true) => State {
+                        known: Vec::new(),
+                        more: true,
+                    },
+                };
+            }
+            Instr::ShowStack | Instr::ClearScreen | Instr::FnDef => {}
+            // host functions can do anything to the stack
+            Instr::FnCall(_) => *state = State::unknown(),
+
+            Instr::Syscall => {
+                self.need(inst, span, state, 2)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                self.pop(inst, span, state, Kind::Number)?;
+                // syscall 0 exits and every other one is an error
+                return Ok(false);
+            }
+            Instr::Exit => return Ok(false),
+
+            Instr::IfStmt(body) => {
+                self.pop(inst, span, state, Kind::Bool)?;
+                if let Some(after) = self.body(body, map.body(idx), types, state.clone()) {
+                    *state = state.join(&after);
+                }
+            }
+            Instr::Loop(body) => {
+                self.pop(inst, span, state, Kind::Number)?;
+                // run the body until the state stops changing; joining only
+                // ever loses information, so this ends
+                while let Some(after) = self.body(body, map.body(idx), types, state.clone()) {
+                    let joined = state.join(&after);
+                    if joined == *state {
+                        break;
+                    }
+                    *state = joined;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reports `inst` if the stack definitely holds fewer than `n` values.
+    fn need(
+        &mut self,
+        inst: &Instr,
+        span: Option<Span>,
+        state: &State,
+        n: usize,
+    ) -> Result<(), Stop> {
+        if !state.more && state.known.len() < n {
+            let needs = if n == 1 {
+                "a value".to_string()
+            } else {
+                format!("{n} values")
+            };
+            let has = match state.known.len() {
+                0 => "is empty".to_string(),
+                len => format!("only has {len}"),
+            };
+            self.report(inst, span, format!("needs {needs}, but the stack {has}"));
+            return Err(Stop);
+        }
+        Ok(())
+    }
+
+    fn pop(
+        &mut self,
+        inst: &Instr,
+        span: Option<Span>,
+        state: &mut State,
+        want: Kind,
+    ) -> Result<Kind, Stop> {
+        self.need(inst, span, state, 1)?;
+        let got = state.known.pop().unwrap_or(Kind::Any);
+        if got != Kind::Any && !got.is(want) {
+            self.report(inst, span, format!("expects {want}, but got {got}"));
+            return Err(Stop);
+        }
+        Ok(got)
+    }
+
+    fn report(&mut self, inst: &Instr, span: Option<Span>, msg: String) {
+        self.add(Severity::Error, inst, span, msg);
+    }
+
+    fn warn(&mut self, inst: &Instr, span: Option<Span>, msg: String) {
+        self.add(Severity::Warning, inst, span, msg);
+    }
+
+    fn add(&mut self, severity: Severity, inst: &Instr, span: Option<Span>, msg: String) {
+        let message = format!("[{}] {msg}.", inst.name());
+        let diagnostic = Diagnostic { severity, span, message };
+        // loop bodies are checked more than once
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn problems(src: &str) -> Vec<(usize, String)> {
+        let (instructions, map) = parse_spanned(src.into(), &ParseOptions::default()).unwrap();
+        check(&instructions, &map)
+            .into_iter()
+            .map(|d| (d.span.unwrap().start, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn finds_definite_errors() {
+        assert_eq!(
+            problems("'hi'P 1 2 + P i n 3 > ? ['big'P] 5 ('x'p) P"),
+            [(
+                42,
+                "[PrintLn] needs a value, but the stack is empty.".into()
+            )]
+        );
+        assert_eq!(
+            problems("1 'a' +"),
+            [(6, "[Plus] expects a Number, but got a String.".into())]
+        );
+        assert_eq!(
+            problems("1 ? ['x' r]"),
+            [(2, "[IfStmt] expects a Bool, but got a Number.".into())]
+        );
+        assert_eq!(
+            problems("1 1 = ? [5 !] 2 (1 r)"),
+            [
+                (11, "[Not] expects a Bool, but got a Number.".into()),
+                (19, "[Reverse] expects a String, but got a Number.".into()),
+            ]
+        );
+        // after an error the stack is unknown, so these aren't reported again
+        assert_eq!(problems("d + P").len(), 1);
+    }
+
+    #[test]
+    fn allows_maybes() {
+        // the stack may or may not have grown in the if and the loop
+        assert!(problems("i n 0 > ? [1] d").is_empty());
+        assert!(problems("i n (1) 'a' P P").is_empty());
+        assert!(problems("i n 5 . + P").is_empty());
+        assert!(problems("1 2 :host +").is_empty());
+        assert!(problems("q P").is_empty());
+
+        for example in ["hello-world.bng", "math-1.bng", "input.bng", "input-math.bng"] {
+            assert!(problems(&crate::test::example_source(example)).is_empty());
+        }
+    }
+
+    #[test]
+    fn infers_types() {
+        assert_eq!(
+            problems("i n 1 + n 'a' 1 = n 1 ? [i r i n]"),
+            [
+                (8, "[ParseNum] got a Number, which is already numeric.".into()),
+                (18, "[ParseNum] got a Bool, which is already numeric.".into()),
+                (22, "[IfStmt] expects a Bool, but got a Number.".into()),
+            ]
+        );
+
+        let src = "i 2 (1 1 = ? ['x']) 'y' i r q P";
+        let (instructions, map) = parse_spanned(src.into(), &ParseOptions::default()).unwrap();
+        let (diagnostics, types) = infer(&instructions, &map);
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            types.annotate(&instructions),
+            "\
+Read                             ; []
+PushNum(2.0)                     ; [Input]
+Loop                             ; [Input, Number]
+    PushNum(1.0)                 ; [.., String]
+    PushNum(1.0)                 ; [.., String, Number]
+    Eq                           ; [.., String, Number, Number]
+    IfStmt                       ; [.., String, Bool]
+        PushStr(\"x\")             ; [.., String]
+PushStr(\"y\")                     ; [.., String]
+Read                             ; [.., String, String]
+Reverse                          ; [.., String, String, Input]
+Exit                             ; [.., String, String, String]
+PrintLn                          ; unreachable
+"
+        );
+    }
+
+    #[test]
+    fn annotates_bodies_never_entered() {
+        let annotate = |src: &str| {
+            let (instructions, map) = parse_spanned(src.into(), &ParseOptions::default()).unwrap();
+            infer(&instructions, &map).1.annotate(&instructions)
+        };
+        assert_eq!(
+            annotate("q 1 1 = ? ['x'P]"),
+            "\
+Exit                             ; []
+PushNum(1.0)                     ; unreachable
+PushNum(1.0)                     ; unreachable
+Eq                               ; unreachable
+IfStmt                           ; unreachable
+    PushStr(\"x\")                 ; unreachable
+    PrintLn                      ; unreachable
+"
+        );
+        assert_eq!(
+            annotate("1 2 + ? [3] P"),
+            "\
+PushNum(1.0)                     ; []
+PushNum(2.0)                     ; [Number]
+Plus                             ; [Number, Number]
+IfStmt                           ; [Number]
+    PushNum(3.0)                 ; unreachable
+PrintLn                          ; [..]
+"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+use std::time::Duration;
+
+use bingbang::clock::Clock;
+use bingbang::compile::Target;
+use bingbang::sandbox::Capability;
+use bingbang::Limits;
+
+pub const HELP: &str = "\
+bingbang - a stack-based esoteric programming language
+
+usage:
+    bingbang [command] [options] <file | ->
+    bingbang [command] [options] -e '<code>'
+
+commands:
+    run        run a program (default)
+    repl       start the interactive interpreter
+    check      find errors in a program without running it
+    debug      step through a program (type `help` at its prompt)
+    dump       print the parsed instructions
+    compile    compile a program for another target (see --target)
+
+options:
+    -e, --eval <code>    use <code> as the program instead of a file
+    -o, --output <path>  output path for `compile` (default: out.rs, out.s,
+                         out.c, out.wat, or out.bngc with --bytecode)
+    -t, --target <name>  `compile` target:
+                           rust  Rust source with only t and T for time,
+                                 build with `rustc -O out.rs`
+                           asm   x86-64 Linux assembly, build with
+                                 `as out.s -o out.o && ld out.o -o out`
+                           c     C99 source with only t and T for time,
+                                 build with `cc out.c -o out -lm`
+                           wat   WebAssembly text for numeric programs, with
+                                 print/read imported from the host
+        --bytecode       `compile` to a .bngc file that `run` accepts
+    -O, --optimize       fold constants before running or compiling; with
+                         `dump`, print the program before and after
+        --types          `dump` the stack inferred before each instruction
+        --fuel <n>       stop a program after <n> instructions
+        --max-stack <n>  stop a program with more than <n> values on its
+                         stack
+        --max-depth <n>  stop a program nesting ifs and loops more than
+                         <n> deep
+        --max-string <n> stop a program making a string over <n> bytes
+        --timeout <secs> stop a program running longer than <secs>
+        --fixed-time <time>
+                         make `t`, `T`, `w`, `e` and `E` always see
+                         <time>, an RFC 3339 time like
+                         2024-02-29T12:00:00+01:00
+        --sandbox        deny a program every capability below
+        --deny <name>    deny a program one capability: `exit` (q and
+                         syscall 0), `clock` (t, T, w, e, E and
+                         'local' z), `stdin` (i) or `terminal` (C)
+    -q, --quiet          don't print banners and file headers
+        --vm             run on the bytecode vm instead of the tree walker
+        --trace          log every instruction `run` performs, with the
+                         stack before and after it, to stderr
+        --trace-file <path>
+                         like --trace, but as JSON lines in <path>
+        --profile        count and time the instructions `run` performs,
+                         and print the slowest to stderr
+        --profile-folded <path>
+                         like --profile, and write folded stacks for flame
+                         graph tools to <path>
+        --no-color       disable colored output
+    -h, --help           print this help
+    -V, --version        print the version
+
+exit codes:
+    0    success, or the code passed to syscall 0 (`<code> 0 $`)
+    1    runtime error, or problems found by `check`
+    2    parse error
+    3    i/o error
+    4    a limit (--fuel, --max-*, --timeout) was exceeded
+    5    a denied capability was used
+    64   invalid command line
+
+time formats for `T`, `F` and `W`:
+    %Y %C %y           year, century, year in century
+    %m %b %B %h        month: 07, Jul, July, Jul
+    %d %e              day of the month: 08, ` 8`
+    %a %A %w %u        weekday: Sun, Sunday, 0 (Sunday) to 6, 1 (Monday)
+                       to 7
+    %j %U %W %G %g %V  day of the year, weeks, ISO 8601 year and week
+    %H %I %k %l        hour: 00 to 23, 01 to 12, ` 0` to 23, ` 1` to 12
+    %M %S              minute, second
+    %f %.f %3f %.3f    nanoseconds, fraction of a second
+    %p %P              AM or PM, am or pm
+    %z %:z %Z          offset: +0930, +09:30, zone name
+    %s                 seconds since 1970-01-01 UTC
+    %D %F %T %R %r     %m/%d/%y, %Y-%m-%d, %H:%M:%S, %H:%M, %I:%M:%S %p
+    %c %x %X %v        date and time, date, time, %e-%b-%Y
+    %t %n %%           tab, newline, %
+    %-d %_d %0d        pad a number with nothing, spaces or zeros
+
+a file name of `-` reads the program from stdin. stdin is then used up,
+so programs that read input (`i`) should be given as a file or with -e:
+
+    echo hello | bingbang -e \"i r P\"
+";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Repl,
+    Check,
+    Debug,
+    Dump,
+    Compile,
+    Help,
+    Version,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    File(String),
+    Stdin,
+    Inline(String),
+}
+
+/// Where `--trace` logs to.
+#[derive(Debug, PartialEq)]
+pub enum Trace {
+    Stderr,
+    File(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub source: Option<Source>,
+    pub output: Option<String>,
+    pub quiet: bool,
+    pub color: bool,
+    pub vm: bool,
+    pub bytecode: bool,
+    pub target: Target,
+    pub optimize: bool,
+    pub types: bool,
+    pub trace: Option<Trace>,
+    pub profile: bool,
+    /// Where `--profile` writes folded stacks.
+    pub folded: Option<String>,
+    pub limits: Limits,
+    pub denied: Vec<Capability>,
+    pub clock: Clock,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options {
+        command: Command::Run,
+        source: None,
+        output: None,
+        quiet: false,
+        color: std::env::var_os("NO_COLOR").is_none(),
+        vm: false,
+        bytecode: false,
+        target: Target::Rust,
+        optimize: false,
+        types: false,
+        trace: None,
+        profile: false,
+        folded: None,
+        limits: Limits::default(),
+        denied: Vec::new(),
+        clock: Clock::Real,
+    };
+
+    let mut seen_command = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => opts.command = Command::Help,
+            "-V" | "--version" => opts.command = Command::Version,
+            "-q" | "--quiet" => opts.quiet = true,
+            "--no-color" => opts.color = false,
+            "--vm" => opts.vm = true,
+            "--bytecode" => opts.bytecode = true,
+            "-O" | "--optimize" => opts.optimize = true,
+            "--types" => opts.types = true,
+            "--trace" => opts.trace = Some(Trace::Stderr),
+            "--trace-file" => {
+                let path = args.next().ok_or(format!("`{arg}` expects an argument."))?;
+                opts.trace = Some(Trace::File(path.clone()));
+            }
+            "--profile" => opts.profile = true,
+            "--profile-folded" => {
+                let path = args.next().ok_or(format!("`{arg}` expects an argument."))?;
+                opts.profile = true;
+                opts.folded = Some(path.clone());
+            }
+            "--fuel" => opts.limits.fuel = Some(number(arg, args.next())?),
+            "--max-stack" => opts.limits.stack = Some(number(arg, args.next())?),
+            "--max-depth" => opts.limits.depth = Some(number(arg, args.next())?),
+            "--max-string" => opts.limits.string = Some(number(arg, args.next())?),
+            "--timeout" => {
+                let secs = number::<f64>(arg, args.next())?;
+                let time = Duration::try_from_secs_f64(secs)
+                    .map_err(|_| format!("`{arg}` expects a number of seconds, got `{secs}`."))?;
+                opts.limits.time = Some(time);
+            }
+            "--fixed-time" => {
+                let time = args.next().ok_or(format!("`{arg}` expects an argument."))?;
+                opts.clock = Clock::parse_fixed(time)?;
+            }
+            "--sandbox" => opts.denied = Capability::ALL.to_vec(),
+            "--deny" => {
+                let name = args.next().ok_or(format!("`{arg}` expects an argument."))?;
+                let capability =
+                    Capability::from_name(name).ok_or(format!("unknown capability `{name}`."))?;
+                if !opts.denied.contains(&capability) {
+                    opts.denied.push(capability);
+                }
+            }
+            "-e" | "--eval" => {
+                let code = args.next().ok_or(format!("`{arg}` expects an argument."))?;
+                set_source(&mut opts, Source::Inline(code.clone()))?;
+            }
+            "-t" | "--target" => {
+                let name = args.next().ok_or(format!("`{arg}` expects an argument."))?;
+                opts.target = Target::from_name(name).ok_or(format!("unknown target `{name}`."))?;
+            }
+            "-o" | "--output" => {
+                let path = args.next().ok_or(format!("`{arg}` expects an argument."))?;
+                opts.output = Some(path.clone());
+            }
+            // kept for compatibility with `bingbang -i`
+            "-i" => opts.command = Command::Repl,
+            "-" => set_source(&mut opts, Source::Stdin)?,
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`.")),
+            _ if !seen_command && opts.source.is_none() && command(arg).is_some() => {
+                opts.command = command(arg).unwrap();
+                seen_command = true;
+            }
+            _ => set_source(&mut opts, Source::File(arg.clone()))?,
+        }
+    }
+
+    if matches!(opts.command, Command::Help | Command::Version) {
+        return Ok(opts);
+    }
+
+    // both watch the tree walker, one at a time
+    for (set, flag) in [(opts.trace.is_some(), "--trace"), (opts.profile, "--profile")] {
+        if set && opts.command != Command::Run {
+            return Err(format!("`{flag}` only works with `run`."));
+        }
+        if set && opts.vm {
+            return Err(format!("`{flag}` doesn't work with `--vm`."));
+        }
+    }
+    if opts.trace.is_some() && opts.profile {
+        return Err("`--trace` and `--profile` can't be used together.".into());
+    }
+
+    match (&opts.command, &opts.source) {
+        (Command::Repl, Some(_)) => Err("`repl` does not take a program.".into()),
+        (Command::Repl, None) => Ok(opts),
+        (_, None) => Err("no program given.".into()),
+        _ => Ok(opts),
+    }
+}
+
+fn command(arg: &str) -> Option<Command> {
+    match arg {
+        "run" => Some(Command::Run),
+        "repl" => Some(Command::Repl),
+        "check" => Some(Command::Check),
+        "debug" => Some(Command::Debug),
+        "dump" => Some(Command::Dump),
+        "compile" => Some(Command::Compile),
+        _ => None,
+    }
+}
+
+/// Parses the value given to the option `arg`.
+fn number<T: std::str::FromStr>(arg: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or(format!("`{arg}` expects an argument."))?;
+    value
+        .parse()
+        .map_err(|_| format!("`{arg}` expects a number, got `{value}`."))
+}
+
+fn set_source(opts: &mut Options, source: Source) -> Result<(), String> {
+    if opts.source.is_some() {
+        return Err("more than one program given.".into());
+    }
+    opts.source = Some(source);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(&args.iter().map(|a| a.to_string()).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn subcommands() {
+        let opts = parse(&["dump", "-q", "--no-color", "foo.bng"]).unwrap();
+        assert_eq!(opts.command, Command::Dump);
+        assert_eq!(opts.source, Some(Source::File("foo.bng".into())));
+        assert!(opts.quiet);
+        assert!(!opts.color);
+
+        assert_eq!(parse(&["foo.bng"]).unwrap().command, Command::Run);
+        assert_eq!(parse(&["-i"]).unwrap().command, Command::Repl);
+        assert_eq!(parse(&["check", "-"]).unwrap().source, Some(Source::Stdin));
+        assert_eq!(parse(&["debug", "a.bng"]).unwrap().command, Command::Debug);
+        assert_eq!(parse(&["-q", "dump", "-e", "1P"]).unwrap().command, Command::Dump);
+    }
+
+    #[test]
+    fn vm_option() {
+        assert!(!parse(&["foo.bng"]).unwrap().vm);
+        assert!(parse(&["--vm", "foo.bng"]).unwrap().vm);
+    }
+
+    #[test]
+    fn bytecode_option() {
+        let opts = parse(&["compile", "--bytecode", "foo.bng", "-o", "foo.bngc"]).unwrap();
+        assert!(opts.bytecode);
+        assert_eq!(opts.output.as_deref(), Some("foo.bngc"));
+    }
+
+    #[test]
+    fn target_option() {
+        assert_eq!(parse(&["compile", "-t", "asm", "a.bng"]).unwrap().target, Target::Asm);
+        assert_eq!(parse(&["compile", "--target", "c", "a.bng"]).unwrap().target, Target::C);
+        assert!(parse(&["compile", "-t", "cobol", "a.bng"]).is_err());
+    }
+
+    #[test]
+    fn optimize_option() {
+        assert!(parse(&["dump", "-O", "foo.bng"]).unwrap().optimize);
+    }
+
+    #[test]
+    fn types_option() {
+        assert!(parse(&["dump", "--types", "foo.bng"]).unwrap().types);
+    }
+
+    #[test]
+    fn trace_options() {
+        assert_eq!(parse(&["--trace", "foo.bng"]).unwrap().trace, Some(Trace::Stderr));
+        assert_eq!(
+            parse(&["run", "foo.bng", "--trace-file", "t.jsonl"]).unwrap().trace,
+            Some(Trace::File("t.jsonl".into()))
+        );
+    }
+
+    #[test]
+    fn profile_options() {
+        assert!(parse(&["--profile", "foo.bng"]).unwrap().profile);
+        let opts = parse(&["--profile-folded", "out.folded", "foo.bng"]).unwrap();
+        assert!(opts.profile);
+        assert_eq!(opts.folded.as_deref(), Some("out.folded"));
+    }
+
+    #[test]
+    fn limit_options() {
+        let opts = parse(&["--fuel", "100", "--timeout", "0.5", "--max-string", "9", "a.bng"]).unwrap();
+        assert_eq!(
+            opts.limits,
+            Limits {
+                fuel: Some(100),
+                string: Some(9),
+                time: Some(Duration::from_millis(500)),
+                ..Limits::default()
+            }
+        );
+    }
+
+    #[test]
+    fn sandbox_options() {
+        assert_eq!(parse(&["--sandbox", "a.bng"]).unwrap().denied, Capability::ALL);
+        let opts = parse(&["--deny", "clock", "--deny", "stdin", "--deny", "clock", "a.bng"]).unwrap();
+        assert_eq!(opts.denied, [Capability::Clock, Capability::Stdin]);
+    }
+
+    #[test]
+    fn fixed_time_option() {
+        let opts = parse(&["--fixed-time", "2024-01-01T00:00:00Z", "a.bng"]).unwrap();
+        assert!(matches!(opts.clock, Clock::Fixed(_)));
+    }
+
+    #[test]
+    fn help_lists_valid_time_formats() {
+        let section = HELP.lines().skip_while(|l| !l.starts_with("time formats"));
+        let lines = section.skip(1).take_while(|l| !l.is_empty());
+        let specs = lines.filter_map(|l| l.trim().split("  ").next().filter(|s| s.starts_with('%')));
+        let specs = specs.flat_map(str::split_whitespace).collect::<Vec<_>>();
+        assert!(specs.len() > 40, "{specs:?}");
+        for spec in specs {
+            assert_eq!(bingbang::time::check_format(spec), Ok(()), "{spec}");
+        }
+    }
+
+    #[test]
+    fn bad_args() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["run", "a.bng", "b.bng"]).is_err());
+        assert!(parse(&["run", "-e"]).is_err());
+        assert!(parse(&["repl", "a.bng"]).is_err());
+        assert!(parse(&["--bogus", "a.bng"]).is_err());
+        assert!(parse(&["dump", "--trace", "a.bng"]).is_err());
+        assert!(parse(&["--vm", "--trace", "a.bng"]).is_err());
+        assert!(parse(&["--trace-file"]).is_err());
+        assert!(parse(&["check", "--profile", "a.bng"]).is_err());
+        assert!(parse(&["--profile", "--trace", "a.bng"]).is_err());
+        assert!(parse(&["--fuel", "lots", "a.bng"]).is_err());
+        assert!(parse(&["--deny", "network", "a.bng"]).is_err());
+        assert!(parse(&["--fixed-time", "noon", "a.bng"]).is_err());
+        assert!(parse(&["--timeout", "-1", "a.bng"]).is_err());
+        assert_eq!(parse(&["--help"]).unwrap().command, Command::Help);
+    }
+}
@@ -0,0 +1,74 @@
+//! Where `t`, `T` and the instructions in [`crate::time`] get the time
+//! from. A fixed clock makes programs that print dates reproducible:
+//!
+//! ```
+//! use bingbang::{clock::Clock, Interpreter};
+//!
+//! let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+//! interpreter.set_clock(Clock::parse_fixed("2024-02-29T12:00:00+01:00").unwrap());
+//! interpreter.run("t P").unwrap();
+//! assert_eq!(interpreter.output(), b"29-02-2024\n");
+//! ```
+
+use std::io::{BufRead, Write};
+
+use chrono::{DateTime, Duration, FixedOffset, Local};
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Clock {
+    /// The system clock in the local timezone.
+    #[default]
+    Real,
+    /// Always the same time, in its own offset.
+    Fixed(DateTime<FixedOffset>),
+    /// The system clock moved by a duration.
+    Offset(Duration),
+}
+
+impl Clock {
+    /// A fixed clock at an RFC 3339 time, like `2024-02-29T12:00:00+01:00`.
+    pub fn parse_fixed(time: &str) -> Result<Self, String> {
+        DateTime::parse_from_rfc3339(time)
+            .map(Clock::Fixed)
+            .map_err(|e| format!("`{time}` is not an RFC 3339 time ({e})."))
+    }
+
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        match self {
+            Clock::Real => Local::now().into(),
+            Clock::Fixed(time) => *time,
+            Clock::Offset(by) => DateTime::<FixedOffset>::from(Local::now()) + *by,
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clocks() {
+        let fixed = Clock::parse_fixed("2001-02-03T04:05:06Z").unwrap();
+        let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+        interpreter.set_clock(fixed);
+        interpreter.run("'%H:%M:%S %z' T P t P").unwrap();
+        assert_eq!(interpreter.output(), b"04:05:06 +0000\n03-02-2001\n");
+
+        assert!(Clock::parse_fixed("yesterday").is_err());
+
+        let ahead = Clock::Offset(Duration::days(2)).now() - Clock::Real.now();
+        assert!((ahead - Duration::days(2)).num_seconds().abs() < 5);
+    }
+}
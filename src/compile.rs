@@ -1,32 +1,81 @@
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::path::Path;
 
 use crate::*;
 
-pub fn compile(instructions: &mut Vec<Instr>) {
-    todo!();
+mod asm;
+mod c;
+mod rust;
+mod wat;
 
-    if std::path::Path::new("out.rs").is_file() {
-        std::fs::remove_file("out.rs").unwrap();
-    }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// A standalone Rust program for Unix covering every instruction but the
+    /// ones in [`crate::time`], see [`compile`].
+    Rust,
+    /// x86-64 assembly for Linux, covering numbers, strings, arithmetic,
+    /// comparisons, ifs, loops, printing and exiting.
+    Asm,
+    /// A single C99 file for any `cc`, covering every instruction but the
+    /// ones in [`crate::time`].
+    C,
+    /// A WebAssembly text module for numbers, bools, arithmetic,
+    /// comparisons, ifs and loops, printing through imported functions.
+    Wat,
+}
 
-    let mut out_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open("out.rs")
-        .unwrap();
+impl Target {
+    pub fn from_name(name: &str) -> Option<Target> {
+        match name {
+            "rust" => Some(Target::Rust),
+            "asm" => Some(Target::Asm),
+            "c" => Some(Target::C),
+            "wat" => Some(Target::Wat),
+            _ => None,
+        }
+    }
 
-    if !instructions.ends_with(&[Instr::Exit]) {
-        instructions.push(Instr::Exit);
+    pub fn default_output(&self) -> &'static str {
+        match self {
+            Target::Rust => "out.rs",
+            Target::Asm => "out.s",
+            Target::C => "out.c",
+            Target::Wat => "out.wat",
+        }
     }
 
-    for instr in instructions.iter() {
-        match instr {
-            Instr::PushNum(n) => {
-                writeln!(out_file, "{}", format!("; --- push {n} to stack\nPUSH {}", *n as i32));
-            },
-            Instr::Exit => todo!(),
-            _ => todo!()
+    /// Returns the program's source in the target language. Instructions the
+    /// target can't express are rejected here.
+    pub fn emit(&self, instructions: &[Instr]) -> Result<String, BngError> {
+        match self {
+            Target::Rust => rust::emit(instructions),
+            Target::Asm => asm::emit(instructions),
+            Target::C => c::emit(instructions),
+            Target::Wat => wat::emit(instructions),
         }
     }
 }
+
+/// Transpiles `instructions` into a standalone Rust program at `out_path`.
+/// `rustc` turns it into a native binary that behaves like the interpreter.
+pub fn compile(instructions: &[Instr], out_path: &Path) -> Result<(), BngError> {
+    compile_to(instructions, Target::Rust, out_path)
+}
+
+pub fn compile_to(instructions: &[Instr], target: Target, out_path: &Path) -> Result<(), BngError> {
+    let out = target.emit(instructions)?;
+
+    std::fs::write(out_path, out)
+        .map_err(|e| BngError::Io(format!("failed to write `{}`: {e}", out_path.display())))
+}
+
+/// Runs [`time::check_c_format`] on the format of the [`Instr::TimeFmt`] at
+/// `idx` when the instruction before it pushes a constant one. Formats only
+/// known at run time go to `strftime` unchecked.
+fn check_time_format(instructions: &[Instr], idx: usize) -> Result<(), BngError> {
+    match idx.checked_sub(1).map(|i| &instructions[i]) {
+        Some(Instr::PushStr(format)) => time::check_c_format(format).map_err(|e| {
+            BngError::Runtime(format!("while compiling [TimeFmt] at index {idx}, {e}"))
+        }),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,174 @@
+//! Emits x86-64 assembly for Linux (GNU as, Intel syntax) for the numeric,
+//! string and printing subset of the language. The output needs no libc:
+//!
+//! ```text
+//! as out.s -o out.o && ld out.o -o out
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::*;
+
+const RUNTIME: &str = include_str!("asm_runtime.s");
+
+pub fn emit(instructions: &[Instr]) -> Result<String, BngError> {
+    let mut emitter = Emitter::default();
+    emitter.body(instructions)?;
+
+    let mut out = String::from(
+        "# generated by bingbang\n    .intel_syntax noprefix\n    .globl _start\n\n    .text\n_start:\n    lea r12, [rip + bng_stack]\n",
+    );
+    out.push_str(&emitter.code);
+    out.push_str("    xor edi, edi\n    jmp bng_exit\n\n");
+    out.push_str(RUNTIME);
+
+    out.push_str("\n    .data\n\n");
+    for name in &emitter.names {
+        writeln!(out, "bng_name_{name}: .quad {}\n    .ascii \"{name}\"", name.len()).unwrap();
+    }
+    for (id, s) in emitter.strings.iter().enumerate() {
+        writeln!(out, "bng_str_{id}: .quad {}\n    .ascii \"{}\"", s.len(), escape(s)).unwrap();
+    }
+    Ok(out)
+}
+
+#[derive(Default)]
+struct Emitter {
+    code: String,
+    strings: Vec<String>,
+    names: BTreeSet<&'static str>,
+    labels: usize,
+}
+
+impl Emitter {
+    fn body(&mut self, instructions: &[Instr]) -> Result<(), BngError> {
+        for (idx, inst) in instructions.iter().enumerate() {
+            if *inst == Instr::FnDef {
+                continue;
+            }
+
+            let name = inst.name();
+            self.names.insert(name);
+            self.op(&format!("lea r14, [rip + bng_name_{name}]"));
+            self.op(&format!("mov r15d, {idx}"));
+
+            match inst {
+                Instr::PushNum(n) => {
+                    self.op("xor eax, eax");
+                    self.op(&format!("mov edx, {:#x}", n.to_bits()));
+                    self.op("call bng_push");
+                }
+                Instr::PushStr(s) => {
+                    self.op("mov eax, 1");
+                    self.op(&format!("lea rdx, [rip + bng_str_{}]", self.strings.len()));
+                    self.op("call bng_push");
+                    self.strings.push(s.clone());
+                }
+                Instr::PushBool(b) => {
+                    self.op(&format!("mov eax, {}", *b as u8));
+                    self.op("call bng_push_bool");
+                }
+                Instr::Plus | Instr::Sub | Instr::Mul | Instr::Div => {
+                    let op = match inst {
+                        Instr::Plus => "addss",
+                        Instr::Sub => "subss",
+                        Instr::Mul => "mulss",
+                        _ => "divss",
+                    };
+                    self.op("call bng_pop_num");
+                    self.op("movss xmm1, xmm0");
+                    self.op("call bng_pop_num");
+                    self.op(&format!("{op} xmm1, xmm0"));
+                    self.op("movss xmm0, xmm1");
+                    self.op("call bng_push_num");
+                }
+                Instr::GreaterThan | Instr::LessThan => {
+                    self.op("call bng_pop_num");
+                    self.op("movss xmm1, xmm0");
+                    self.op("call bng_pop_num");
+                    if *inst == Instr::GreaterThan {
+                        self.op("comiss xmm1, xmm0");
+                    } else {
+                        self.op("comiss xmm0, xmm1");
+                    }
+                    self.op("seta al");
+                    self.op("movzx eax, al");
+                    self.op("call bng_push_bool");
+                }
+                Instr::Eq => self.op("call bng_eq"),
+                Instr::Not => {
+                    self.op("call bng_pop_bool");
+                    self.op("xor eax, 1");
+                    self.op("call bng_push_bool");
+                }
+                Instr::Sum => self.op("call bng_sum"),
+                Instr::Print => self.op("call bng_print"),
+                Instr::PrintLn => self.op("call bng_println"),
+                Instr::Pop => self.op("call bng_drop"),
+                Instr::ClearStack => self.op("lea r12, [rip + bng_stack]"),
+                Instr::ClearScreen => self.op("call bng_clear_screen"),
+                Instr::Syscall => self.op("call bng_syscall"),
+                Instr::Exit => {
+                    self.op("xor edi, edi");
+                    self.op("jmp bng_exit");
+                }
+                Instr::IfStmt(body) => {
+                    let end = self.label();
+                    self.op("call bng_pop_bool");
+                    self.op("test eax, eax");
+                    self.op(&format!("jz .Lend_{end}"));
+                    self.body(body)?;
+                    self.code.push_str(&format!(".Lend_{end}:\n"));
+                }
+                Instr::Loop(body) => {
+                    let start = self.label();
+                    self.op("call bng_pop_num");
+                    self.op("cvttss2si rax, xmm0");
+                    self.op("push rax");
+                    self.code.push_str(&format!(".Lloop_{start}:\n"));
+                    self.op("cmp qword ptr [rsp], 0");
+                    self.op(&format!("jle .Lend_{start}"));
+                    self.body(body)?;
+                    self.op("dec qword ptr [rsp]");
+                    self.op(&format!("jmp .Lloop_{start}"));
+                    self.code.push_str(&format!(".Lend_{start}:\n"));
+                    self.op("add rsp, 8");
+                }
+                _ => {
+                    return Err(BngError::Runtime(format!(
+                        "the asm target doesn't support [{name}] (at index {idx})."
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn op(&mut self, line: &str) {
+        self.code.push_str("    ");
+        self.code.push_str(line);
+        self.code.push('\n');
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels
+    }
+}
+
+/// Escapes `s` for a GNU as `.ascii` directive.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7e => out.push(b as char),
+            _ => write!(out, "\\{b:03o}").unwrap(),
+        }
+    }
+    out
+}
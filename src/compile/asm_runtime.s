@@ -0,0 +1,539 @@
+# runtime of programs compiled by the bingbang x86-64 backend.
+#
+# values are 16 bytes on a stack in .bss: a tag (0 number, 1 string,
+# 2 bool) and a payload (f32 bits, the address of a string record, or 0/1).
+# string records are a u64 length followed by the bytes.
+#
+# registers kept across the program:
+#   r12  next free slot of the value stack
+#   r14  name record of the current instruction, for errors
+#   r15  index of the current instruction, for errors
+
+    .text
+
+# rdi = exit code
+bng_exit:
+    mov eax, 60
+    syscall
+
+# rdi = fd, rsi = ptr, rdx = len
+bng_write:
+    test rdx, rdx
+    jz 2f
+1:
+    mov eax, 1
+    syscall
+    test rax, rax
+    js 2f
+    add rsi, rax
+    sub rdx, rax
+    jnz 1b
+2:
+    ret
+
+# rsi = string record
+bng_write_record:
+    mov rdx, [rsi]
+    add rsi, 8
+    jmp bng_write
+
+# rsi = record with the reason; prints the error and exits with 1
+bng_error:
+    call bng_report
+    mov edi, 1
+    jmp bng_exit
+
+# rsi = record with the reason; prints the error and returns
+bng_report:
+    push rsi
+    mov edi, 2
+    lea rsi, [rip + bng_s_err_head]
+    call bng_write_record
+    mov edi, 2
+    mov rsi, r14
+    call bng_write_record
+    mov edi, 2
+    lea rsi, [rip + bng_s_err_at]
+    call bng_write_record
+    mov rax, r15
+    call bng_fmt_u64
+    mov edi, 2
+    call bng_write
+    mov edi, 2
+    lea rsi, [rip + bng_s_err_sep]
+    call bng_write_record
+    pop rsi
+    mov edi, 2
+    call bng_write_record
+    mov edi, 2
+    lea rsi, [rip + bng_s_nl]
+    jmp bng_write_record
+
+# rax = value; returns rsi = ptr, rdx = len of its decimal digits
+bng_fmt_u64:
+    lea rsi, [rip + bng_intbuf_end]
+    mov ecx, 10
+1:
+    xor edx, edx
+    div rcx
+    add dl, '0'
+    dec rsi
+    mov [rsi], dl
+    test rax, rax
+    jnz 1b
+    lea rdx, [rip + bng_intbuf_end]
+    sub rdx, rsi
+    ret
+
+# rax = tag, rdx = payload
+bng_push:
+    lea rcx, [rip + bng_stack_end]
+    cmp r12, rcx
+    jae 1f
+    mov [r12], rax
+    mov [r12 + 8], rdx
+    add r12, 16
+    ret
+1:
+    lea rsi, [rip + bng_s_overflow]
+    jmp bng_error
+
+# returns rax = tag, rdx = payload
+bng_pop:
+    lea rcx, [rip + bng_stack]
+    cmp r12, rcx
+    jbe 1f
+    sub r12, 16
+    mov rax, [r12]
+    mov rdx, [r12 + 8]
+    ret
+1:
+    lea rsi, [rip + bng_s_empty]
+    jmp bng_error
+
+# returns xmm0 = number
+bng_pop_num:
+    call bng_pop
+    test rax, rax
+    jnz 1f
+    movd xmm0, edx
+    ret
+1:
+    lea rsi, [rip + bng_s_not_num]
+    jmp bng_error
+
+# returns eax = 0 or 1
+bng_pop_bool:
+    call bng_pop
+    cmp rax, 2
+    jne 1f
+    mov eax, edx
+    ret
+1:
+    lea rsi, [rip + bng_s_not_bool]
+    jmp bng_error
+
+# xmm0 = number
+bng_push_num:
+    xor eax, eax
+    movd edx, xmm0
+    jmp bng_push
+
+# eax = 0 or 1
+bng_push_bool:
+    mov edx, eax
+    mov eax, 2
+    jmp bng_push
+
+# rax = tag, rdx = payload; returns rsi = ptr, rdx = len
+bng_format:
+    cmp rax, 1
+    je 1f
+    cmp rax, 2
+    je 2f
+    movd xmm0, edx
+    jmp bng_fmt_num
+1:
+    lea rsi, [rdx + 8]
+    mov rdx, [rdx]
+    ret
+2:
+    lea rsi, [rip + bng_s_false]
+    test edx, edx
+    jz 3f
+    lea rsi, [rip + bng_s_true]
+3:
+    mov rdx, [rsi]
+    add rsi, 8
+    ret
+
+bng_print:
+    call bng_pop
+    call bng_format
+    mov edi, 1
+    jmp bng_write
+
+bng_println:
+    call bng_print
+    mov edi, 1
+    lea rsi, [rip + bng_s_nl]
+    jmp bng_write_record
+
+# rcx = n >= 0; returns xmm2 = 10^n
+bng_pow10:
+    mov rax, 0x3ff0000000000000
+    movq xmm2, rax
+    mov rax, 0x4024000000000000
+    movq xmm5, rax
+    test rcx, rcx
+    jz 2f
+1:
+    mulsd xmm2, xmm5
+    dec rcx
+    jnz 1b
+2:
+    ret
+
+# xmm3 = xmm3 * 10^(-rcx), for any sign of rcx
+bng_shift10:
+    push rcx
+    test rcx, rcx
+    js 1f
+    call bng_pow10
+    divsd xmm3, xmm2
+    pop rcx
+    ret
+1:
+    neg rcx
+    call bng_pow10
+    mulsd xmm3, xmm2
+    pop rcx
+    ret
+
+# xmm0 = f32; returns rsi = ptr, rdx = len. prints the shortest decimal
+# that reads back as the same f32, without exponent, like rust's Display.
+bng_fmt_num:
+    push rbx
+    lea rdi, [rip + bng_numbuf]
+    movd eax, xmm0
+    ucomiss xmm0, xmm0
+    jp .Lnum_nan
+    btr eax, 31
+    jnc 1f
+    mov byte ptr [rdi], '-'
+    inc rdi
+1:
+    cmp eax, 0x7f800000
+    je .Lnum_inf
+    test eax, eax
+    jz .Lnum_zero
+
+    mov r8d, eax
+    movd xmm0, eax
+    cvtss2sd xmm1, xmm0
+
+    # r9 = e = floor(log10 x)
+    xor r9, r9
+2:
+    mov rcx, r9
+    inc rcx
+    movsd xmm3, xmm1
+    call bng_shift10
+    mov rax, 0x3ff0000000000000
+    movq xmm4, rax
+    # x * 10^-(e+1) >= 1 -> e too small
+    ucomisd xmm3, xmm4
+    jb 3f
+    inc r9
+    jmp 2b
+3:
+    mov rcx, r9
+    movsd xmm3, xmm1
+    call bng_shift10
+    ucomisd xmm3, xmm4
+    jae 4f
+    dec r9
+    jmp 3b
+4:
+    # r10 = p, the number of significant digits
+    mov r10, 1
+5:
+    # k = e + 1 - p, scaled = round(x / 10^k)
+    mov rcx, r9
+    inc rcx
+    sub rcx, r10
+    movsd xmm3, xmm1
+    call bng_shift10
+    roundsd xmm3, xmm3, 0
+    movsd xmm6, xmm3
+    neg rcx
+    call bng_shift10
+    cvtsd2ss xmm4, xmm3
+    movd eax, xmm4
+    cmp eax, r8d
+    je 6f
+    inc r10
+    cmp r10, 9
+    jb 5b
+    # 9 digits always read back; recompute them
+    mov rcx, r9
+    inc rcx
+    sub rcx, r10
+    movsd xmm3, xmm1
+    call bng_shift10
+    roundsd xmm6, xmm3, 0
+6:
+    # rounding up may have produced p + 1 digits, ending in 0
+    mov rcx, r10
+    call bng_pow10
+    cvtsd2si r11, xmm2
+    cvtsd2si rax, xmm6
+    cmp rax, r11
+    jb 7f
+    xor edx, edx
+    mov ecx, 10
+    div rcx
+    inc r9
+7:
+    # digits of the scaled value, p of them
+    mov rbx, rdi
+    call bng_fmt_u64
+    mov rdi, rbx
+    # rsi = digits, rdx = p
+    test r9, r9
+    js .Lnum_small
+    mov rcx, r9
+    inc rcx
+    cmp rcx, rdx
+    jae .Lnum_int
+    # d.ddd
+    sub rdx, rcx
+    rep movsb
+    mov byte ptr [rdi], '.'
+    inc rdi
+    mov rcx, rdx
+    rep movsb
+    jmp .Lnum_done
+.Lnum_int:
+    # ddd000
+    sub rcx, rdx
+    mov rbx, rcx
+    mov rcx, rdx
+    rep movsb
+    mov rcx, rbx
+    mov al, '0'
+    rep stosb
+    jmp .Lnum_done
+.Lnum_small:
+    # 0.000ddd
+    mov word ptr [rdi], 0x2e30
+    add rdi, 2
+    mov rcx, r9
+    not rcx
+    mov al, '0'
+    rep stosb
+    mov rcx, rdx
+    rep movsb
+    jmp .Lnum_done
+.Lnum_zero:
+    mov byte ptr [rdi], '0'
+    inc rdi
+    jmp .Lnum_done
+.Lnum_inf:
+    mov dword ptr [rdi], 0x00666e69
+    add rdi, 3
+    jmp .Lnum_done
+.Lnum_nan:
+    lea rdi, [rip + bng_numbuf]
+    mov dword ptr [rdi], 0x004e614e
+    add rdi, 3
+.Lnum_done:
+    lea rsi, [rip + bng_numbuf]
+    mov rdx, rdi
+    sub rdx, rsi
+    pop rbx
+    ret
+
+# rsi/rdx and rdi/rcx are two byte strings; returns eax = 1 if equal
+bng_bytes_eq:
+    xor eax, eax
+    cmp rdx, rcx
+    jne 1f
+    repe cmpsb
+    jne 1f
+    mov eax, 1
+1:
+    ret
+
+# pops two values and pushes whether they are equal, comparing across
+# types like the interpreter: 1 == true, 1 == '1', true == 'true'
+bng_eq:
+    push rbx
+    push rbp
+    call bng_pop
+    mov rbx, rax
+    mov rbp, rdx
+    call bng_pop
+    # order the pair by tag: (rax, rdx) <= (rbx, rbp)
+    cmp rax, rbx
+    jbe 1f
+    xchg rax, rbx
+    xchg rdx, rbp
+1:
+    cmp rax, rbx
+    jne .Leq_mixed
+    cmp rax, 1
+    je .Leq_str
+    cmp rax, 2
+    je .Leq_bits
+    # numbers: NaN is never equal
+    movd xmm0, edx
+    movd xmm1, ebp
+    ucomiss xmm0, xmm1
+    setnp al
+    sete cl
+    and al, cl
+    movzx eax, al
+    jmp .Leq_done
+.Leq_bits:
+    cmp rdx, rbp
+    sete al
+    movzx eax, al
+    jmp .Leq_done
+.Leq_str:
+    lea rsi, [rdx + 8]
+    mov rdx, [rdx]
+    lea rdi, [rbp + 8]
+    mov rcx, [rbp]
+    call bng_bytes_eq
+    jmp .Leq_done
+.Leq_mixed:
+    test rax, rax
+    jnz .Leq_bool_str
+    cmp rbx, 2
+    je .Leq_num_bool
+    # number and string: compare the formatted number
+    movd xmm0, edx
+    call bng_fmt_num
+    lea rdi, [rbp + 8]
+    mov rcx, [rbp]
+    call bng_bytes_eq
+    jmp .Leq_done
+.Leq_num_bool:
+    movd xmm0, edx
+    cvtsi2ss xmm1, ebp
+    ucomiss xmm0, xmm1
+    setnp al
+    sete cl
+    and al, cl
+    movzx eax, al
+    jmp .Leq_done
+.Leq_bool_str:
+    # rax = string, rbx = bool
+    lea rsi, [rip + bng_s_false]
+    test ebp, ebp
+    jz 2f
+    lea rsi, [rip + bng_s_true]
+2:
+    mov rcx, [rsi]
+    lea rdi, [rsi + 8]
+    lea rsi, [rdx + 8]
+    mov rdx, [rdx]
+    call bng_bytes_eq
+.Leq_done:
+    pop rbp
+    pop rbx
+    jmp bng_push_bool
+
+bng_drop:
+    lea rcx, [rip + bng_stack]
+    cmp r12, rcx
+    jbe 1f
+    sub r12, 16
+1:
+    ret
+
+# adds up the whole stack, top first, like the interpreter does. values
+# that aren't numbers are reported and left out
+bng_sum:
+    lea rcx, [rip + bng_stack]
+    cmp r12, rcx
+    jbe 4f
+    xorps xmm1, xmm1
+1:
+    call bng_pop
+    test rax, rax
+    jnz 2f
+    movd xmm0, edx
+    addss xmm1, xmm0
+    jmp 3f
+2:
+    lea rsi, [rip + bng_s_sum_mixed]
+    call bng_report
+3:
+    lea rcx, [rip + bng_stack]
+    cmp r12, rcx
+    ja 1b
+    movss xmm0, xmm1
+    jmp bng_push_num
+4:
+    lea rsi, [rip + bng_s_empty]
+    jmp bng_error
+
+bng_syscall:
+    call bng_pop_num
+    cvttss2si rax, xmm0
+    test rax, rax
+    jnz 1f
+    call bng_pop_num
+    cvttss2si rdi, xmm0
+    jmp bng_exit
+1:
+    lea rsi, [rip + bng_s_syscall]
+    jmp bng_error
+
+bng_clear_screen:
+    mov edi, 1
+    lea rsi, [rip + bng_s_clear]
+    jmp bng_write_record
+
+    .data
+
+bng_s_err_head: .quad 27
+    .ascii "[ERROR]: while performing ["
+bng_s_err_at: .quad 11
+    .ascii "] at index "
+bng_s_err_sep: .quad 2
+    .ascii ", "
+bng_s_nl: .quad 1
+    .ascii "\n"
+bng_s_overflow: .quad 15
+    .ascii "stack overflow."
+bng_s_empty: .quad 15
+    .ascii "stack is empty."
+bng_s_not_num: .quad 21
+    .ascii "expected type Number."
+bng_s_not_bool: .quad 19
+    .ascii "expected type Bool."
+bng_s_syscall: .quad 26
+    .ascii "unknown syscall argument. "
+bng_s_true: .quad 4
+    .ascii "true"
+bng_s_false: .quad 5
+    .ascii "false"
+bng_s_clear: .quad 7
+    .ascii "\x1b[2J\x1b[H"
+bng_s_sum_mixed: .quad 45
+    .ascii "failed to SUM because non-number is in stack."
+
+    .bss
+
+    .align 16
+bng_stack:
+    .skip 16 * 65536
+bng_stack_end:
+bng_numbuf:
+    .skip 64
+bng_intbuf:
+    .skip 24
+bng_intbuf_end:
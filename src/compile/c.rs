@@ -0,0 +1,130 @@
+//! Translates instructions into a single C file: a tagged-union `Value`
+//! stack like [`StackVal`] and one runtime call per instruction. Any C99
+//! compiler builds it:
+//!
+//! ```text
+//! cc -O2 out.c -o out -lm
+//! ```
+//!
+//! `T` formats with `strftime`, so a constant format with a specifier it
+//! doesn't share with the interpreter is rejected.
+
+use std::fmt::Write;
+
+use crate::*;
+
+const RUNTIME: &str = include_str!("c_runtime.c");
+
+pub fn emit(instructions: &[Instr]) -> Result<String, BngError> {
+    let mut out = String::from(RUNTIME);
+    out.push_str("\nint main(void) {\n");
+    emit_body(instructions, 1, &mut out)?;
+    out.push_str("    bng_exit(0);\n}\n");
+    Ok(out)
+}
+
+fn emit_body(instructions: &[Instr], depth: usize, out: &mut String) -> Result<(), BngError> {
+    let indent = "    ".repeat(depth);
+
+    for (idx, inst) in instructions.iter().enumerate() {
+        let line = match inst {
+            Instr::PushNum(n) => format!("push_num({});", float(*n)),
+            Instr::PushStr(v) => format!("push_str(\"{}\", {});", escape(v), v.len()),
+            Instr::PushBool(b) => format!("push_bool({b});"),
+            Instr::Print => format!("print({idx});"),
+            Instr::PrintLn => format!("println({idx});"),
+            Instr::Read => format!("read_line({idx});"),
+            Instr::Plus | Instr::Sub | Instr::Mul | Instr::Div => {
+                format!("math(\"{}\", {idx});", inst.name())
+            }
+            Instr::GreaterThan | Instr::LessThan => format!("cmp(\"{}\", {idx});", inst.name()),
+            Instr::Sum => format!("sum({idx});"),
+            Instr::ParseNum => format!("parse_num({idx});"),
+            Instr::Reverse => format!("reverse({idx});"),
+            Instr::GenRange => format!("gen_range({idx});"),
+            Instr::FlipStack => "flip_stack();".into(),
+            Instr::Pop => "pop_value();".into(),
+            Instr::ClearScreen => "clear_screen();".into(),
+            Instr::Syscall => format!("do_syscall({idx});"),
+            Instr::Time => "time_now();".into(),
+            Instr::TimeFmt => {
+                super::check_time_format(instructions, idx)?;
+                format!("time_fmt({idx});")
+            }
+            Instr::Eq => format!("eq({idx});"),
+            Instr::Not => format!("not({idx});"),
+            Instr::ClearStack => "clear_stack();".into(),
+            Instr::PrintStack => "print_stack();".into(),
+            Instr::PrintStackLn => "print_stack_ln();".into(),
+            Instr::ShowStack => "show_stack();".into(),
+            Instr::Exit => "bng_exit(0);".into(),
+            Instr::FnDef => continue,
+            Instr::Now
+            | Instr::Epoch
+            | Instr::EpochMillis
+            | Instr::ParseTime
+            | Instr::FormatTime
+            | Instr::AddTime
+            | Instr::TimeDiff
+            | Instr::InZone => {
+                return Err(BngError::Runtime(format!(
+                    "the c target doesn't support [{}] (at index {idx}).",
+                    inst.name()
+                )))
+            }
+            Instr::FnCall(name) => {
+                return Err(BngError::Runtime(format!(
+                    "can't compile [{inst:?}]: host function `{name}` only exists in an embedding interpreter."
+                )))
+            }
+            Instr::IfStmt(body) => {
+                writeln!(out, "{indent}if (cond({idx})) {{").unwrap();
+                emit_body(body, depth + 1, out)?;
+                writeln!(out, "{indent}}}").unwrap();
+                continue;
+            }
+            Instr::Loop(body) => {
+                writeln!(
+                    out,
+                    "{indent}for (long long i{depth} = count({idx}); i{depth} > 0; i{depth}--) {{"
+                )
+                .unwrap();
+                emit_body(body, depth + 1, out)?;
+                writeln!(out, "{indent}}}").unwrap();
+                continue;
+            }
+        };
+        writeln!(out, "{indent}{line}").unwrap();
+    }
+    Ok(())
+}
+
+/// A C float literal for `n`. Rust's `{:?}` is the shortest decimal that
+/// reads back as the same f32, which C's parser then rounds to exactly `n`.
+fn float(n: f32) -> String {
+    if n.is_nan() {
+        "NAN".into()
+    } else if n.is_infinite() {
+        if n > 0.0 { "INFINITY" } else { "-INFINITY" }.into()
+    } else {
+        format!("{n:?}f")
+    }
+}
+
+/// Escapes `s` for a C string literal. Octal escapes are always three digits
+/// so a following digit can't extend them, and `?` is escaped to avoid
+/// trigraphs.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'"' | b'\\' | b'?' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7e => out.push(b as char),
+            _ => write!(out, "\\{b:03o}").unwrap(),
+        }
+    }
+    out
+}
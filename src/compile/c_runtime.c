@@ -0,0 +1,543 @@
+/* runtime of programs compiled by the bingbang c backend. it mirrors the
+ * interpreter in interpret.rs, so keep the two in sync. */
+
+#include <math.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+enum tag { NUMBER, STRING, BOOL };
+
+typedef struct {
+    enum tag tag;
+    union {
+        float num;
+        struct {
+            char *ptr;
+            size_t len;
+        } str;
+        bool b;
+    } as;
+} Value;
+
+static Value *stack;
+static size_t len, cap;
+
+static void bng_exit(int code) {
+    fflush(stdout);
+    exit(code);
+}
+
+static void fail(const char *fmt, ...) {
+    va_list args;
+    fflush(stdout);
+    fputs("[ERROR]: ", stderr);
+    va_start(args, fmt);
+    vfprintf(stderr, fmt, args);
+    va_end(args);
+    fputc('\n', stderr);
+    exit(1);
+}
+
+static void *xalloc(void *ptr, size_t size) {
+    ptr = realloc(ptr, size ? size : 1);
+    if (!ptr)
+        fail("out of memory");
+    return ptr;
+}
+
+static void push(Value v) {
+    if (len == cap) {
+        cap = cap ? cap * 2 : 64;
+        stack = xalloc(stack, cap * sizeof(Value));
+    }
+    stack[len++] = v;
+}
+
+static Value pop(void) {
+    return stack[--len];
+}
+
+static void drop(Value v) {
+    if (v.tag == STRING)
+        free(v.as.str.ptr);
+}
+
+static void push_num(float n) {
+    Value v = { NUMBER, { .num = n } };
+    push(v);
+}
+
+static void push_bool(bool b) {
+    Value v = { BOOL, { .b = b } };
+    push(v);
+}
+
+/* takes ownership of `ptr` */
+static void push_owned(char *ptr, size_t n) {
+    Value v = { STRING, { .str = { ptr, n } } };
+    push(v);
+}
+
+static void push_str(const char *s, size_t n) {
+    char *ptr = xalloc(NULL, n);
+    memcpy(ptr, s, n);
+    push_owned(ptr, n);
+}
+
+/* formats `n` like rust's `{}` does for f32: the shortest decimal that reads
+ * back as the same float, without an exponent. `out` holds at least 64 bytes. */
+static void fmt_num(float n, char *out) {
+    char buf[32], digits[16];
+    int k = 0, e, p;
+    float a = fabsf(n);
+
+    if (isnan(n)) {
+        strcpy(out, "NaN");
+        return;
+    }
+    if (signbit(n))
+        *out++ = '-';
+    if (isinf(n)) {
+        strcpy(out, "inf");
+        return;
+    }
+    if (a == 0) {
+        strcpy(out, "0");
+        return;
+    }
+
+    for (p = 1; p < 9; p++) {
+        snprintf(buf, sizeof buf, "%.*e", p - 1, (double)a);
+        if (strtof(buf, NULL) == a)
+            break;
+    }
+    snprintf(buf, sizeof buf, "%.*e", p - 1, (double)a);
+    for (char *c = buf; *c != 'e'; c++)
+        if (*c != '.')
+            digits[k++] = *c;
+    e = atoi(strchr(buf, 'e') + 1);
+    while (k > 1 && digits[k - 1] == '0')
+        k--;
+
+    if (e < 0) {
+        out += sprintf(out, "0.");
+        for (int i = 0; i < -e - 1; i++)
+            *out++ = '0';
+        memcpy(out, digits, k);
+        out[k] = 0;
+    } else if (k <= e + 1) {
+        memcpy(out, digits, k);
+        memset(out + k, '0', e + 1 - k);
+        out[e + 1] = 0;
+    } else {
+        memcpy(out, digits, e + 1);
+        out[e + 1] = '.';
+        memcpy(out + e + 2, digits + e + 1, k - e - 1);
+        out[k + 1] = 0;
+    }
+}
+
+static void write_value(Value v) {
+    char buf[64];
+    switch (v.tag) {
+    case NUMBER:
+        fmt_num(v.as.num, buf);
+        fputs(buf, stdout);
+        break;
+    case STRING:
+        fwrite(v.as.str.ptr, 1, v.as.str.len, stdout);
+        break;
+    case BOOL:
+        fputs(v.as.b ? "true" : "false", stdout);
+        break;
+    }
+}
+
+/* formats `v` like rust's `{:?}` does for a StackVal, for error messages.
+ * the result is freed by the caller. */
+static char *debug(Value v) {
+    char *out, buf[64];
+    size_t n = 0;
+
+    switch (v.tag) {
+    case NUMBER:
+        out = xalloc(NULL, 80);
+        if (v.as.num != 0 && isfinite(v.as.num)
+            && (fabsf(v.as.num) < 1e-4f || fabsf(v.as.num) >= 1e16f)) {
+            int p;
+            for (p = 1; p < 9; p++) {
+                snprintf(buf, sizeof buf, "%.*e", p - 1, (double)v.as.num);
+                if (strtof(buf, NULL) == v.as.num)
+                    break;
+            }
+            snprintf(buf, sizeof buf, "%.*e", p - 1, (double)v.as.num);
+            /* c writes `1.5e-05`, rust `1.5e-5` */
+            char *e = strchr(buf, 'e');
+            int exp = atoi(e + 1);
+            *e = 0;
+            sprintf(out, "Number(%se%d)", buf, exp);
+        } else {
+            fmt_num(v.as.num, buf);
+            bool plain = isfinite(v.as.num) && !strchr(buf, '.');
+            sprintf(out, "Number(%s%s)", buf, plain ? ".0" : "");
+        }
+        return out;
+    case BOOL:
+        out = xalloc(NULL, 16);
+        sprintf(out, "Bool(%s)", v.as.b ? "true" : "false");
+        return out;
+    case STRING:
+        break;
+    }
+
+    out = xalloc(NULL, v.as.str.len * 10 + 16);
+    n += sprintf(out, "String(\"");
+    for (size_t i = 0; i < v.as.str.len; i++) {
+        unsigned char c = v.as.str.ptr[i];
+        switch (c) {
+        case '"': n += sprintf(out + n, "\\\""); break;
+        case '\\': n += sprintf(out + n, "\\\\"); break;
+        case '\n': n += sprintf(out + n, "\\n"); break;
+        case '\r': n += sprintf(out + n, "\\r"); break;
+        case '\t': n += sprintf(out + n, "\\t"); break;
+        default:
+            if (c < 0x20 || c == 0x7f)
+                n += sprintf(out + n, "\\u{%x}", c);
+            else
+                out[n++] = c;
+        }
+    }
+    sprintf(out + n, "\")");
+    return out;
+}
+
+/* rust's `as` saturates where c's casts are undefined */
+static long long to_i64(float n) {
+    if (isnan(n))
+        return 0;
+    if (n >= 9.223372e18f)
+        return INT64_MAX;
+    if (n <= -9.223372e18f)
+        return INT64_MIN;
+    return (long long)n;
+}
+
+static int to_i32(float n) {
+    if (isnan(n))
+        return 0;
+    if (n >= 2147483648.0f)
+        return INT32_MAX;
+    if (n <= -2147483648.0f)
+        return INT32_MIN;
+    return (int)n;
+}
+
+static void need(size_t n, const char *name, int idx) {
+    if (len < n)
+        fail("while performing [%s] at index %d, stack length too short. (must be >= %zu)",
+             name, idx, n);
+}
+
+static float num(Value v, const char *name, int idx) {
+    if (v.tag != NUMBER)
+        fail("while performing [%s] at index %d, expected type Number, but got %s.",
+             name, idx, debug(v));
+    return v.as.num;
+}
+
+static bool boolean(Value v, const char *name, int idx) {
+    if (v.tag != BOOL)
+        fail("while performing [%s] at index %d, expected type Bool, but got %s.",
+             name, idx, debug(v));
+    return v.as.b;
+}
+
+static void print(int idx) {
+    need(1, "Print", idx);
+    Value v = pop();
+    write_value(v);
+    drop(v);
+    fflush(stdout);
+}
+
+static void println(int idx) {
+    need(1, "PrintLn", idx);
+    Value v = pop();
+    write_value(v);
+    putchar('\n');
+    drop(v);
+}
+
+static void math(const char *name, int idx) {
+    need(2, name, idx);
+    float a = num(pop(), name, idx);
+    float b = num(pop(), name, idx);
+    switch (name[0]) {
+    case 'P': push_num(a + b); break;
+    case 'S': push_num(a - b); break;
+    case 'M': push_num(a * b); break;
+    default: push_num(a / b); break;
+    }
+}
+
+static void cmp(const char *name, int idx) {
+    need(2, name, idx);
+    float a = num(pop(), name, idx);
+    float b = num(pop(), name, idx);
+    push_bool(name[0] == 'G' ? a > b : a < b);
+}
+
+static void sum(int idx) {
+    float total = 0;
+    need(1, "Sum", idx);
+    while (len) {
+        Value v = pop();
+        if (v.tag == NUMBER)
+            total += v.as.num;
+        else
+            fprintf(stderr, "[ERROR]: while performing [Sum] at index %d, failed to SUM because non-number is in stack.\n", idx);
+        drop(v);
+    }
+    push_num(total);
+}
+
+static void read_line(int idx) {
+    size_t n = 0, size = 64;
+    char *buf = xalloc(NULL, size);
+    int c;
+
+    fflush(stdout);
+    while ((c = getchar()) != EOF) {
+        if (n + 1 == size)
+            buf = xalloc(buf, size *= 2);
+        buf[n++] = c;
+        if (c == '\n')
+            break;
+    }
+    if (ferror(stdin))
+        fail("while performing [Read] at index %d, failed to read stdin.", idx);
+    if (n == 0)
+        fail("while performing [Read] at index %d, reached end of input.", idx);
+    if (n && buf[n - 1] == '\n')
+        n--;
+    if (n && buf[n - 1] == '\r')
+        n--;
+    push_owned(buf, n);
+}
+
+static bool is_space(char c) {
+    return c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '\f' || c == '\v';
+}
+
+static void parse_num(int idx) {
+    need(1, "ParseNum", idx);
+    Value v = pop();
+    if (v.tag == NUMBER) {
+        push(v);
+        return;
+    }
+    if (v.tag == BOOL) {
+        push_num(v.as.b);
+        return;
+    }
+
+    char *s = v.as.str.ptr, *end = s + v.as.str.len, *parsed;
+    while (s < end && is_space(*s))
+        s++;
+    while (end > s && is_space(end[-1]))
+        end--;
+    if (s == end)
+        fail("while trying to [ParseNum] at index %d, failed to parse because of error: cannot parse float from empty string", idx);
+
+    /* strtof also takes hex floats and `infinity`, which rust doesn't */
+    size_t n = end - s;
+    char *text = xalloc(NULL, n + 1);
+    memcpy(text, s, n);
+    text[n] = 0;
+    bool hex = strchr(text, 'x') || strchr(text, 'X');
+    float f = strtof(text, &parsed);
+    if (hex || parsed != text + n || (isnan(f) && strcmp(text, "NaN") != 0))
+        fail("while trying to [ParseNum] at index %d, failed to parse because of error: invalid float literal", idx);
+    free(text);
+    drop(v);
+    push_num(f);
+}
+
+static bool str_is(Value v, const char *s) {
+    return v.as.str.len == strlen(s) && memcmp(v.as.str.ptr, s, v.as.str.len) == 0;
+}
+
+static bool equal(Value a, Value b) {
+    char buf[64];
+    if (a.tag > b.tag) {
+        Value t = a;
+        a = b;
+        b = t;
+    }
+    switch (a.tag * 3 + b.tag) {
+    case NUMBER * 3 + NUMBER: return a.as.num == b.as.num;
+    case BOOL * 3 + BOOL: return a.as.b == b.as.b;
+    case STRING * 3 + STRING:
+        return a.as.str.len == b.as.str.len
+            && memcmp(a.as.str.ptr, b.as.str.ptr, a.as.str.len) == 0;
+    case NUMBER * 3 + BOOL: return a.as.num == (float)b.as.b;
+    case NUMBER * 3 + STRING:
+        fmt_num(a.as.num, buf);
+        return str_is(b, buf);
+    default: /* STRING, BOOL */
+        return str_is(a, b.as.b ? "true" : "false");
+    }
+}
+
+static void eq(int idx) {
+    need(2, "Eq", idx);
+    Value a = pop();
+    Value b = pop();
+    push_bool(equal(a, b));
+    drop(a);
+    drop(b);
+}
+
+static void not(int idx) {
+    need(1, "Not", idx);
+    push_bool(!boolean(pop(), "Not", idx));
+}
+
+static bool cond(int idx) {
+    need(1, "IfStmt", idx);
+    return boolean(pop(), "IfStmt", idx);
+}
+
+static long long count(int idx) {
+    need(1, "Loop", idx);
+    return to_i64(num(pop(), "Loop", idx));
+}
+
+static void flip_stack(void) {
+    for (size_t i = 0; i < len / 2; i++) {
+        Value t = stack[i];
+        stack[i] = stack[len - 1 - i];
+        stack[len - 1 - i] = t;
+    }
+}
+
+static void pop_value(void) {
+    if (len)
+        drop(pop());
+}
+
+static void clear_stack(void) {
+    while (len)
+        drop(pop());
+}
+
+static void print_stack(void) {
+    if (!len)
+        return;
+    while (len > 1) {
+        Value v = pop();
+        write_value(v);
+        drop(v);
+    }
+    putchar('\n');
+}
+
+static void print_stack_ln(void) {
+    while (len) {
+        Value v = pop();
+        write_value(v);
+        putchar('\n');
+        drop(v);
+    }
+}
+
+static void show_stack(void) {
+    for (size_t i = 0; i < len; i++) {
+        write_value(stack[i]);
+        putchar('\n');
+    }
+}
+
+static void do_syscall(int idx) {
+    char buf[64];
+    need(2, "Syscall", idx);
+    float call = num(pop(), "Syscall", idx);
+    Value arg = pop();
+    if (to_i32(call) == 0)
+        bng_exit(to_i32(num(arg, "Syscall", idx)));
+    fmt_num(call, buf);
+    fail("at index %d: unkown syscall argument: %s", idx, buf);
+}
+
+/* c.rs only lets constant formats through when strftime formats them like
+   the interpreter */
+static void push_date(const char *fmt) {
+    time_t now = time(NULL);
+    struct tm *local = localtime(&now);
+    size_t size = 64, n;
+    char *buf = NULL;
+
+    do {
+        buf = xalloc(buf, size *= 2);
+        n = strftime(buf, size, fmt, local);
+    } while (n == 0 && *fmt && size < 65536);
+    push_owned(buf, n);
+}
+
+static void time_now(void) {
+    push_date("%d-%m-%Y");
+}
+
+static void time_fmt(int idx) {
+    need(1, "TimeFmt", idx);
+    Value v = pop();
+    if (v.tag != STRING)
+        fail("while trying to perform [TimeFmt] at index %d, expected type String, but got %s.",
+             idx, debug(v));
+    char *fmt = xalloc(NULL, v.as.str.len + 1);
+    memcpy(fmt, v.as.str.ptr, v.as.str.len);
+    fmt[v.as.str.len] = 0;
+    push_date(fmt);
+    free(fmt);
+    drop(v);
+}
+
+/* reverses the characters, not the bytes, of a utf-8 string */
+static void reverse(int idx) {
+    need(1, "Reverse", idx);
+    Value v = pop();
+    if (v.tag != STRING)
+        fail("while trying to perform [Reverse] at index %d, expected String(), got %s",
+             idx, debug(v));
+
+    size_t n = v.as.str.len, out = n;
+    char *s = v.as.str.ptr, *rev = xalloc(NULL, n);
+    for (size_t i = 0; i < n;) {
+        size_t w = 1;
+        while (i + w < n && (s[i + w] & 0xc0) == 0x80)
+            w++;
+        out -= w;
+        memcpy(rev + out, s + i, w);
+        i += w;
+    }
+    drop(v);
+    push_owned(rev, n);
+}
+
+static void gen_range(int idx) {
+    need(2, "GenRange", idx);
+    int from = to_i32(num(pop(), "GenRange", idx));
+    int to = to_i32(num(pop(), "GenRange", idx));
+    for (int i = from; i < to; i++)
+        push_num((float)i);
+}
+
+static void clear_screen(void) {
+    fputs("\x1b[2J\x1b[H", stdout);
+    fflush(stdout);
+}
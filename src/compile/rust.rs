@@ -0,0 +1,104 @@
+//! Transpiles instructions into a standalone Rust program: a `Vec<Value>`
+//! stack and one runtime call per instruction. Build it with `rustc`.
+//!
+//! `t` and `T` read the local time from libc, so the program builds on
+//! Unix. `T` formats with `strftime`, so a constant format with a specifier
+//! it doesn't share with the interpreter is rejected, as are the
+//! instructions in [`crate::time`].
+
+use std::fmt::Write;
+
+use crate::*;
+
+const RUNTIME: &str = include_str!("rust_runtime.rs");
+
+pub fn emit(instructions: &[Instr]) -> Result<String, BngError> {
+    let mut out = String::from(RUNTIME);
+    out.push_str("\nfn main() {\n    let s = &mut Stack::new();\n");
+    emit_body(instructions, 1, &mut out)?;
+    out.push_str("    exit(0);\n}\n");
+    Ok(out)
+}
+
+fn emit_body(instructions: &[Instr], depth: usize, out: &mut String) -> Result<(), BngError> {
+    let indent = "    ".repeat(depth);
+
+    for (idx, inst) in instructions.iter().enumerate() {
+        let line = match inst {
+            Instr::PushNum(n) => format!("push_num(s, {});", float(*n)),
+            Instr::PushStr(v) => format!("push_str(s, {v:?});"),
+            Instr::PushBool(b) => format!("s.push(Value::Bool({b}));"),
+            Instr::Print => format!("print(s, {idx});"),
+            Instr::PrintLn => format!("println(s, {idx});"),
+            Instr::Read => format!("read(s, {idx});"),
+            Instr::Plus | Instr::Sub | Instr::Mul | Instr::Div => {
+                format!("math(s, \"{inst:?}\", {idx});")
+            }
+            Instr::GreaterThan | Instr::LessThan => format!("cmp(s, \"{inst:?}\", {idx});"),
+            Instr::Sum => format!("sum(s, {idx});"),
+            Instr::ParseNum => format!("parse_num(s, {idx});"),
+            Instr::Reverse => format!("reverse(s, {idx});"),
+            Instr::GenRange => format!("gen_range(s, {idx});"),
+            Instr::FlipStack => "s.reverse();".into(),
+            Instr::Pop => "s.pop();".into(),
+            Instr::ClearScreen => "clear_screen();".into(),
+            Instr::Syscall => format!("syscall(s, {idx});"),
+            Instr::Eq => format!("eq(s, {idx});"),
+            Instr::Not => format!("not(s, {idx});"),
+            Instr::ClearStack => "s.clear();".into(),
+            Instr::PrintStack => "print_stack(s);".into(),
+            Instr::PrintStackLn => "print_stack_ln(s);".into(),
+            Instr::ShowStack => "show_stack(s);".into(),
+            Instr::Exit => "exit(0);".into(),
+            Instr::FnDef => continue,
+            Instr::Time => "time_now(s);".into(),
+            Instr::TimeFmt => {
+                super::check_time_format(instructions, idx)?;
+                format!("time_fmt(s, {idx});")
+            }
+            Instr::Now
+            | Instr::Epoch
+            | Instr::EpochMillis
+            | Instr::ParseTime
+            | Instr::FormatTime
+            | Instr::AddTime
+            | Instr::TimeDiff
+            | Instr::InZone => {
+                return Err(BngError::Runtime(format!(
+                    "the rust target doesn't support [{}] (at index {idx}).",
+                    inst.name()
+                )))
+            }
+            Instr::FnCall(name) => {
+                return Err(BngError::Runtime(format!(
+                    "can't compile [{inst:?}]: host function `{name}` only exists in an embedding interpreter."
+                )))
+            }
+            Instr::IfStmt(body) => {
+                writeln!(out, "{indent}if cond(s, {idx}) {{").unwrap();
+                emit_body(body, depth + 1, out)?;
+                writeln!(out, "{indent}}}").unwrap();
+                continue;
+            }
+            Instr::Loop(body) => {
+                writeln!(out, "{indent}for _ in 0..count(s, {idx}) {{").unwrap();
+                emit_body(body, depth + 1, out)?;
+                writeln!(out, "{indent}}}").unwrap();
+                continue;
+            }
+        };
+        writeln!(out, "{indent}{line}").unwrap();
+    }
+    Ok(())
+}
+
+/// A Rust literal for `n`, which `{:?}` doesn't give for infinities and NaN.
+fn float(n: f32) -> String {
+    if n.is_nan() {
+        "f32::NAN".into()
+    } else if n.is_infinite() {
+        if n > 0.0 { "f32::INFINITY" } else { "f32::NEG_INFINITY" }.into()
+    } else {
+        format!("{n:?}")
+    }
+}
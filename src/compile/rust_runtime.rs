@@ -0,0 +1,284 @@
+// runtime of programs compiled by the bingbang rust backend. it mirrors the
+// interpreter in interpret.rs, so keep the two in sync.
+
+#![allow(dead_code)]
+
+use std::io::{BufRead, Write};
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(f32),
+    String(String),
+    Bool(bool),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Number(n), Value::Bool(b)) | (Value::Bool(b), Value::Number(n)) => {
+                *n == (*b as i8) as f32
+            }
+            (Value::Number(n), Value::String(s)) | (Value::String(s), Value::Number(n)) => {
+                n.to_string() == *s
+            }
+            (Value::Bool(b), Value::String(s)) | (Value::String(s), Value::Bool(b)) => {
+                b.to_string() == *s
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => write!(f, "{s}"),
+            Value::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+type Stack = Vec<Value>;
+
+fn fail(msg: String) -> ! {
+    std::io::stdout().flush().ok();
+    eprintln!("[ERROR]: {msg}");
+    std::process::exit(1);
+}
+
+fn exit(code: i32) -> ! {
+    std::io::stdout().flush().ok();
+    std::process::exit(code);
+}
+
+fn need(s: &Stack, n: usize, name: &str, idx: usize) {
+    if s.len() < n {
+        fail(format!(
+            "while performing [{name}] at index {idx}, stack length too short. (must be >= {n})"
+        ));
+    }
+}
+
+fn num(v: Value, name: &str, idx: usize) -> f32 {
+    match v {
+        Value::Number(n) => n,
+        v => fail(format!(
+            "while performing [{name}] at index {idx}, expected type Number, but got {v:?}."
+        )),
+    }
+}
+
+fn bool(v: Value, name: &str, idx: usize) -> bool {
+    match v {
+        Value::Bool(b) => b,
+        v => fail(format!(
+            "while performing [{name}] at index {idx}, expected type Bool, but got {v:?}."
+        )),
+    }
+}
+
+fn push_num(s: &mut Stack, n: f32) {
+    s.push(Value::Number(n));
+}
+
+fn push_str(s: &mut Stack, v: &str) {
+    s.push(Value::String(v.to_string()));
+}
+
+fn print(s: &mut Stack, idx: usize) {
+    need(s, 1, "Print", idx);
+    print!("{}", s.pop().unwrap());
+    std::io::stdout().flush().ok();
+}
+
+fn println(s: &mut Stack, idx: usize) {
+    need(s, 1, "PrintLn", idx);
+    println!("{}", s.pop().unwrap());
+}
+
+fn math(s: &mut Stack, name: &str, idx: usize) {
+    need(s, 2, name, idx);
+    let a = num(s.pop().unwrap(), name, idx);
+    let b = num(s.pop().unwrap(), name, idx);
+    s.push(Value::Number(match name {
+        "Plus" => a + b,
+        "Sub" => a - b,
+        "Mul" => a * b,
+        _ => a / b,
+    }));
+}
+
+fn cmp(s: &mut Stack, name: &str, idx: usize) {
+    need(s, 2, name, idx);
+    let a = num(s.pop().unwrap(), name, idx);
+    let b = num(s.pop().unwrap(), name, idx);
+    s.push(Value::Bool(if name == "GreaterThan" { a > b } else { a < b }));
+}
+
+fn sum(s: &mut Stack, idx: usize) {
+    need(s, 1, "Sum", idx);
+    let mut total = 0.0;
+    while let Some(v) = s.pop() {
+        match v {
+            Value::Number(n) => total += n,
+            _ => eprintln!(
+                "[ERROR]: while performing [Sum] at index {idx}, failed to SUM because non-number is in stack."
+            ),
+        }
+    }
+    s.push(Value::Number(total));
+}
+
+fn read(s: &mut Stack, idx: usize) {
+    let mut buf = String::new();
+    match std::io::stdin().lock().read_line(&mut buf) {
+        Ok(0) => fail(format!("while performing [Read] at index {idx}, reached end of input.")),
+        Ok(_) => (),
+        Err(e) => fail(format!("while performing [Read] at index {idx}, failed to read stdin: {e}")),
+    }
+    let line = buf.strip_suffix('\n').unwrap_or(&buf);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    s.push(Value::String(line.to_string()));
+}
+
+fn parse_num(s: &mut Stack, idx: usize) {
+    need(s, 1, "ParseNum", idx);
+    let n = match s.pop().unwrap() {
+        Value::Number(n) => n,
+        Value::Bool(b) => (b as i8) as f32,
+        Value::String(v) => v.trim().parse::<f32>().unwrap_or_else(|e| {
+            fail(format!(
+                "while trying to [ParseNum] at index {idx}, failed to parse because of error: {e}"
+            ))
+        }),
+    };
+    s.push(Value::Number(n));
+}
+
+fn eq(s: &mut Stack, idx: usize) {
+    need(s, 2, "Eq", idx);
+    let a = s.pop().unwrap();
+    let b = s.pop().unwrap();
+    s.push(Value::Bool(a == b));
+}
+
+fn not(s: &mut Stack, idx: usize) {
+    need(s, 1, "Not", idx);
+    let b = bool(s.pop().unwrap(), "Not", idx);
+    s.push(Value::Bool(!b));
+}
+
+fn cond(s: &mut Stack, idx: usize) -> bool {
+    need(s, 1, "IfStmt", idx);
+    bool(s.pop().unwrap(), "IfStmt", idx)
+}
+
+fn count(s: &mut Stack, idx: usize) -> i64 {
+    need(s, 1, "Loop", idx);
+    num(s.pop().unwrap(), "Loop", idx) as i64
+}
+
+fn print_stack(s: &mut Stack) {
+    if s.is_empty() {
+        return;
+    }
+    while s.len() > 1 {
+        print!("{}", s.pop().unwrap());
+    }
+    println!();
+}
+
+fn print_stack_ln(s: &mut Stack) {
+    while let Some(v) = s.pop() {
+        println!("{v}");
+    }
+}
+
+fn show_stack(s: &mut Stack) {
+    for v in s.iter() {
+        println!("{v}");
+    }
+}
+
+fn syscall(s: &mut Stack, idx: usize) {
+    need(s, 2, "Syscall", idx);
+    let call = num(s.pop().unwrap(), "Syscall", idx);
+    let arg = s.pop().unwrap();
+    match call as i32 {
+        0 => exit(num(arg, "Syscall", idx) as i32),
+        _ => fail(format!("at index {idx}: unkown syscall argument: {call}")),
+    }
+}
+
+fn reverse(s: &mut Stack, idx: usize) {
+    need(s, 1, "Reverse", idx);
+    match s.pop().unwrap() {
+        Value::String(v) => s.push(Value::String(v.chars().rev().collect())),
+        v => fail(format!(
+            "while trying to perform [Reverse] at index {idx}, expected String(), got {v:?}"
+        )),
+    }
+}
+
+fn gen_range(s: &mut Stack, idx: usize) {
+    need(s, 2, "GenRange", idx);
+    let from = num(s.pop().unwrap(), "GenRange", idx) as i32;
+    let to = num(s.pop().unwrap(), "GenRange", idx) as i32;
+    for i in from..to {
+        s.push(Value::Number(i as f32));
+    }
+}
+
+// std can't tell the local time, so ask libc. `struct tm` is only handed
+// from localtime_r to strftime, so a buffer at least as big stands in for it.
+#[repr(C, align(8))]
+struct Tm([u8; 128]);
+
+extern "C" {
+    fn time(t: *mut std::os::raw::c_long) -> std::os::raw::c_long;
+    fn localtime_r(t: *const std::os::raw::c_long, tm: *mut Tm) -> *mut Tm;
+    fn strftime(s: *mut u8, max: usize, format: *const std::os::raw::c_char, tm: *const Tm) -> usize;
+}
+
+// emit only lets constant formats through when strftime formats them like
+// the interpreter. like C, the format ends at a nul
+fn date(fmt: &str) -> String {
+    let fmt = std::ffi::CString::new(fmt.split('\0').next().unwrap()).unwrap();
+    let mut tm = Tm([0; 128]);
+    let mut buf = vec![0u8; 128];
+    let n = loop {
+        let n = unsafe {
+            let now = time(std::ptr::null_mut());
+            localtime_r(&now, &mut tm);
+            strftime(buf.as_mut_ptr(), buf.len(), fmt.as_ptr(), &tm)
+        };
+        if n > 0 || fmt.as_bytes().is_empty() || buf.len() >= 65536 {
+            break n;
+        }
+        buf.resize(buf.len() * 2, 0);
+    };
+    buf.truncate(n);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+fn time_now(s: &mut Stack) {
+    s.push(Value::String(date("%d-%m-%Y")));
+}
+
+fn time_fmt(s: &mut Stack, idx: usize) {
+    need(s, 1, "TimeFmt", idx);
+    match s.pop().unwrap() {
+        Value::String(fmt) => s.push(Value::String(date(&fmt))),
+        v => fail(format!(
+            "while trying to perform [TimeFmt] at index {idx}, expected type String, but got {v:?}."
+        )),
+    }
+}
+
+fn clear_screen() {
+    print!("\x1b[2J\x1b[H");
+    std::io::stdout().flush().ok();
+}
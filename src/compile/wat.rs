@@ -0,0 +1,155 @@
+//! Emits a WebAssembly text module for the numeric subset of the language:
+//! numbers, bools, arithmetic, comparisons, ifs and loops. Strings are
+//! rejected, and `i` reads a number.
+//!
+//! The module exports `memory` and `main`, and imports from `bng`:
+//!
+//! ```text
+//! print_num (f32)           print a number, formatted like the interpreter
+//! print_bool (i32)          print `true` or `false`
+//! newline ()                end the line
+//! read_num () -> f32        read a line of input as a number
+//! exit (i32)                exit with a code, `main` returns right after
+//! fail (name, idx, reason)  report a runtime error, then `main` traps
+//! ```
+//!
+//! For `fail`, `name` points at the instruction's name in memory, a length
+//! byte followed by the bytes, and `reason` is one of: 0 stack too short,
+//! 1 expected a Number, 2 expected a Bool, 3 stack overflow, 4 unknown
+//! syscall.
+
+use std::fmt::Write;
+
+use crate::*;
+
+const RUNTIME: &str = include_str!("wat_runtime.wat");
+
+pub fn emit(instructions: &[Instr]) -> Result<String, BngError> {
+    let mut emitter = Emitter::default();
+    emitter.body(instructions, 1, 0)?;
+
+    let mut out = String::from(";; generated by bingbang\n(module\n");
+    out.push_str(RUNTIME);
+
+    out.push_str("\n  (data (i32.const 0) \"");
+    for name in &emitter.names {
+        write!(out, "\\{:02x}{name}", name.len()).unwrap();
+    }
+    out.push_str("\")\n\n  (func (export \"main\")\n");
+    for depth in 1..=emitter.depth {
+        writeln!(out, "    (local $l{depth} i64)").unwrap();
+    }
+    out.push_str(&emitter.code);
+    out.push_str("  )\n)\n");
+    Ok(out)
+}
+
+#[derive(Default)]
+struct Emitter {
+    code: String,
+    /// Names of the instructions used, in memory order.
+    names: Vec<&'static str>,
+    /// Deepest loop nesting, each level has its own counter local.
+    depth: usize,
+    labels: usize,
+}
+
+impl Emitter {
+    fn body(&mut self, instructions: &[Instr], level: usize, loops: usize) -> Result<(), BngError> {
+        let indent = "  ".repeat(level + 1);
+
+        for (idx, inst) in instructions.iter().enumerate() {
+            let call = match inst {
+                Instr::PushNum(n) => format!("(call $push_num (f32.const {}))", float(*n)),
+                Instr::PushBool(b) => format!("(call $push_bool (i32.const {}))", *b as u8),
+                Instr::Plus => "(call $plus)".into(),
+                Instr::Sub => "(call $sub)".into(),
+                Instr::Mul => "(call $mul)".into(),
+                Instr::Div => "(call $div)".into(),
+                Instr::GreaterThan => "(call $gt)".into(),
+                Instr::LessThan => "(call $lt)".into(),
+                Instr::Eq => "(call $eq)".into(),
+                Instr::Not => "(call $not)".into(),
+                Instr::Sum => "(call $sum)".into(),
+                Instr::ParseNum => "(call $parse_num)".into(),
+                Instr::Read => "(call $read)".into(),
+                Instr::Print => "(call $print)".into(),
+                Instr::PrintLn => "(call $println)".into(),
+                Instr::Pop => "(call $drop)".into(),
+                Instr::ClearStack => "(call $clear)".into(),
+                Instr::Syscall => "(call $syscall)\n(return)".into(),
+                Instr::Exit => "(call $exit (i32.const 0))\n(return)".into(),
+                Instr::IfStmt(_) | Instr::Loop(_) => String::new(),
+                Instr::FnDef => continue,
+                _ => {
+                    return Err(BngError::Runtime(format!(
+                        "the wat target doesn't support [{}] (at index {idx}).",
+                        inst.name()
+                    )))
+                }
+            };
+
+            let name = self.name(inst.name());
+            writeln!(self.code, "{indent}(global.set $name (i32.const {name}))").unwrap();
+            writeln!(self.code, "{indent}(global.set $idx (i32.const {idx}))").unwrap();
+
+            match inst {
+                Instr::IfStmt(body) => {
+                    writeln!(self.code, "{indent}(if (call $pop_bool)\n{indent}  (then").unwrap();
+                    self.body(body, level + 2, loops)?;
+                    writeln!(self.code, "{indent}  ))").unwrap();
+                }
+                Instr::Loop(body) => {
+                    self.labels += 1;
+                    let (label, local) = (self.labels, loops + 1);
+                    self.depth = self.depth.max(local);
+                    writeln!(self.code, "{indent}(local.set $l{local} (call $count))").unwrap();
+                    writeln!(self.code, "{indent}(block $end{label}").unwrap();
+                    writeln!(self.code, "{indent}  (loop $loop{label}").unwrap();
+                    writeln!(
+                        self.code,
+                        "{indent}    (br_if $end{label} (i64.le_s (local.get $l{local}) (i64.const 0)))"
+                    )
+                    .unwrap();
+                    self.body(body, level + 2, local)?;
+                    writeln!(
+                        self.code,
+                        "{indent}    (local.set $l{local} (i64.sub (local.get $l{local}) (i64.const 1)))"
+                    )
+                    .unwrap();
+                    writeln!(self.code, "{indent}    (br $loop{label})))").unwrap();
+                }
+                _ => {
+                    for line in call.lines() {
+                        writeln!(self.code, "{indent}{line}").unwrap();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the memory offset of `name`'s record.
+    fn name(&mut self, name: &'static str) -> usize {
+        let mut offset = 0;
+        for known in &self.names {
+            if *known == name {
+                return offset;
+            }
+            offset += 1 + known.len();
+        }
+        self.names.push(name);
+        offset
+    }
+}
+
+/// A WAT f32 literal for `n`.
+fn float(n: f32) -> String {
+    if n.is_nan() {
+        "nan".into()
+    } else if n.is_infinite() {
+        if n > 0.0 { "inf" } else { "-inf" }.into()
+    } else {
+        format!("{n:?}")
+    }
+}
@@ -0,0 +1,144 @@
+  ;; runtime of programs compiled by the bingbang wat backend. it mirrors the
+  ;; interpreter in interpret.rs, so keep the two in sync.
+  ;;
+  ;; the stack lives in memory from 1024 up, one 8 byte slot per value: an
+  ;; i32 tag (0 Number, 1 Bool) and an f32 (bools are stored as 0 or 1).
+  ;; $name and $idx point at the instruction being run, for errors.
+
+  (import "bng" "print_num" (func $print_num (param f32)))
+  (import "bng" "print_bool" (func $print_bool (param i32)))
+  (import "bng" "newline" (func $newline))
+  (import "bng" "read_num" (func $read_num (result f32)))
+  (import "bng" "exit" (func $exit (param i32)))
+  (import "bng" "fail" (func $fail (param $name i32) (param $idx i32) (param $reason i32)))
+
+  (memory (export "memory") 16)
+
+  (global $sp (mut i32) (i32.const 1024))
+  (global $tag (mut i32) (i32.const 0))
+  (global $name (mut i32) (i32.const 0))
+  (global $idx (mut i32) (i32.const 0))
+
+  (func $error (param $reason i32)
+    (call $fail (global.get $name) (global.get $idx) (local.get $reason))
+    unreachable)
+
+  (func $push (param $tag i32) (param $v f32)
+    (if (i32.ge_u (global.get $sp) (i32.const 1048576))
+      (then (call $error (i32.const 3))))
+    (i32.store (global.get $sp) (local.get $tag))
+    (f32.store offset=4 (global.get $sp) (local.get $v))
+    (global.set $sp (i32.add (global.get $sp) (i32.const 8))))
+
+  (func $push_num (param $v f32)
+    (call $push (i32.const 0) (local.get $v)))
+
+  (func $push_bool (param $b i32)
+    (call $push (i32.const 1) (f32.convert_i32_u (local.get $b))))
+
+  ;; pops any value, leaving its tag in $tag
+  (func $pop (result f32)
+    (if (i32.le_u (global.get $sp) (i32.const 1024))
+      (then (call $error (i32.const 0))))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 8)))
+    (global.set $tag (i32.load (global.get $sp)))
+    (f32.load offset=4 (global.get $sp)))
+
+  (func $pop_num (result f32)
+    (local $v f32)
+    (local.set $v (call $pop))
+    (if (global.get $tag)
+      (then (call $error (i32.const 1))))
+    (local.get $v))
+
+  (func $pop_bool (result i32)
+    (local $v f32)
+    (local.set $v (call $pop))
+    (if (i32.eqz (global.get $tag))
+      (then (call $error (i32.const 2))))
+    (f32.ne (local.get $v) (f32.const 0)))
+
+  (func $plus
+    (local $a f32)
+    (local.set $a (call $pop_num))
+    (call $push_num (f32.add (local.get $a) (call $pop_num))))
+
+  (func $sub
+    (local $a f32)
+    (local.set $a (call $pop_num))
+    (call $push_num (f32.sub (local.get $a) (call $pop_num))))
+
+  (func $mul
+    (local $a f32)
+    (local.set $a (call $pop_num))
+    (call $push_num (f32.mul (local.get $a) (call $pop_num))))
+
+  (func $div
+    (local $a f32)
+    (local.set $a (call $pop_num))
+    (call $push_num (f32.div (local.get $a) (call $pop_num))))
+
+  (func $gt
+    (local $a f32)
+    (local.set $a (call $pop_num))
+    (call $push_bool (f32.gt (local.get $a) (call $pop_num))))
+
+  (func $lt
+    (local $a f32)
+    (local.set $a (call $pop_num))
+    (call $push_bool (f32.lt (local.get $a) (call $pop_num))))
+
+  ;; a Bool equals the Number 0 or 1, so comparing the stored floats is enough
+  (func $eq
+    (local $a f32)
+    (local.set $a (call $pop))
+    (call $push_bool (f32.eq (local.get $a) (call $pop))))
+
+  (func $not
+    (call $push_bool (i32.eqz (call $pop_bool))))
+
+  (func $sum
+    (local $total f32)
+    (if (i32.le_u (global.get $sp) (i32.const 1024))
+      (then (call $error (i32.const 0))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.le_u (global.get $sp) (i32.const 1024)))
+        (local.set $total (f32.add (local.get $total) (call $pop_num)))
+        (br $next)))
+    (call $push_num (local.get $total)))
+
+  ;; a Bool parses to 0 or 1, which is what it's stored as
+  (func $parse_num
+    (call $push_num (call $pop)))
+
+  (func $read
+    (call $push_num (call $read_num)))
+
+  (func $print
+    (local $v f32)
+    (local.set $v (call $pop))
+    (if (global.get $tag)
+      (then (call $print_bool (i32.trunc_f32_u (local.get $v))))
+      (else (call $print_num (local.get $v)))))
+
+  (func $println
+    (call $print)
+    (call $newline))
+
+  (func $drop
+    (if (i32.gt_u (global.get $sp) (i32.const 1024))
+      (then (global.set $sp (i32.sub (global.get $sp) (i32.const 8))))))
+
+  (func $clear
+    (global.set $sp (i32.const 1024)))
+
+  (func $count (result i64)
+    (i64.trunc_sat_f32_s (call $pop_num)))
+
+  (func $syscall
+    (local $call f32)
+    (local.set $call (call $pop_num))
+    (if (i32.trunc_sat_f32_s (local.get $call))
+      (then (call $error (i32.const 4))))
+    (call $exit (i32.trunc_sat_f32_s (call $pop_num))))
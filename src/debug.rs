@@ -0,0 +1,237 @@
+//! An interactive debugger for the tree walker. It stops before the first
+//! instruction and reads commands from the interpreter's input, so a program
+//! that reads with `i` shares it with the prompt.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use crate::hook::{self, Hook};
+use crate::parse::{SourceMap, Span};
+use crate::*;
+
+pub const HELP: &str = "\
+s, step          run one instruction, stepping into bodies
+n, next          run one instruction, stepping over bodies
+c, continue      run until a breakpoint
+b, break <line>  stop when the program reaches <line>
+d, delete <line> remove the breakpoint on <line>
+p, stack         print the stack
+w, where         show the current instruction again
+q, quit          stop the program
+an empty line repeats the last step, next or continue.
+";
+
+#[derive(Debug)]
+pub struct Debugger {
+    src: String,
+    map: SourceMap,
+    breakpoints: Vec<usize>,
+    mode: Mode,
+    /// The line of the previous instruction, so a breakpoint stops once per
+    /// visit to its line rather than at every instruction on it.
+    line: Option<usize>,
+    repeat: Option<Mode>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Step,
+    /// Stop at the next instruction nested at most this deep.
+    Next(usize),
+    Continue,
+}
+
+impl Debugger {
+    /// A debugger for a program parsed from `src` with `map`. Without a
+    /// source, instructions are shown by their path instead.
+    pub fn new(src: &str, map: SourceMap) -> Self {
+        Self {
+            src: src.into(),
+            map,
+            breakpoints: Vec::new(),
+            mode: Mode::Step,
+            line: None,
+            repeat: None,
+        }
+    }
+
+    fn span(&self, path: &[usize]) -> Option<Span> {
+        self.map.at(path)
+    }
+
+    /// Describes where the program stopped: the instruction, its position
+    /// and the source line with the instruction underlined.
+    fn show(&self, inst: &Instr, path: &[usize]) -> String {
+        let has_body = matches!(inst, Instr::IfStmt(_) | Instr::Loop(_));
+        let inst = format!("[{}]", inst.brief());
+        let Some(span) = self.span(path) else {
+            return format!("-> {} {inst}\n", hook::location(&self.map, &self.src, path));
+        };
+
+        let (line, col) = span.line_col(&self.src);
+        let text = self.src.lines().nth(line - 1).unwrap_or("");
+        let width = match has_body {
+            true => 1,
+            false => (span.end - span.start).max(1),
+        };
+        format!(
+            "-> {line}:{col} {inst}\n    {text}\n    {}{}\n",
+            " ".repeat(col - 1),
+            "^".repeat(width)
+        )
+    }
+
+    /// Runs a command, returning what to do next if it resumes the program.
+    fn command(
+        &mut self,
+        cmd: &str,
+        inst: &Instr,
+        path: &[usize],
+        stack: &[StackVal],
+        out: &mut String,
+    ) -> Option<Resume> {
+        let mut words = cmd.split_whitespace();
+        let (name, arg) = (words.next().unwrap_or(""), words.next());
+        let line = arg.map(|a| a.parse::<usize>().map_err(|_| a));
+
+        match (name, line) {
+            ("", _) => return self.repeat.map(Resume::Run),
+            ("s" | "step", None) => return Some(Resume::Run(Mode::Step)),
+            ("n" | "next", None) => return Some(Resume::Run(Mode::Next(path.len()))),
+            ("c" | "continue", None) => return Some(Resume::Run(Mode::Continue)),
+            ("q" | "quit", None) => return Some(Resume::Quit),
+            ("p" | "stack", None) => writeln!(out, "{stack:?}").unwrap(),
+            ("w" | "where", None) => out.push_str(&self.show(inst, path)),
+            ("h" | "help", None) => out.push_str(HELP),
+            ("b" | "break", Some(Ok(line))) => {
+                if !has_line(&self.map, &self.src, line) {
+                    writeln!(out, "no instruction on line {line}.").unwrap();
+                } else if !self.breakpoints.contains(&line) {
+                    self.breakpoints.push(line);
+                    writeln!(out, "breakpoint on line {line}.").unwrap();
+                }
+            }
+            ("d" | "delete", Some(Ok(line))) => {
+                if self.breakpoints.contains(&line) {
+                    self.breakpoints.retain(|&l| l != line);
+                    writeln!(out, "deleted the breakpoint on line {line}.").unwrap();
+                } else {
+                    writeln!(out, "no breakpoint on line {line}.").unwrap();
+                }
+            }
+            ("b" | "break" | "d" | "delete", None) => {
+                writeln!(out, "`{name}` expects a line.").unwrap()
+            }
+            ("b" | "break" | "d" | "delete", Some(Err(arg))) => {
+                writeln!(out, "`{arg}` is not a line number.").unwrap()
+            }
+            _ => writeln!(out, "unknown command `{}` (see `help`).", cmd.trim()).unwrap(),
+        }
+        None
+    }
+}
+
+enum Resume {
+    Run(Mode),
+    Quit,
+}
+
+impl<R: BufRead, W: Write> Hook<R, W> for Debugger {
+    fn before(
+        &mut self,
+        interpreter: &mut Interpreter<R, W>,
+        inst: &Instr,
+        path: &[usize],
+    ) -> Result<ExecOutcome, String> {
+        let line = self.span(path).map(|span| span.line_col(&self.src).0);
+        let entered = line.filter(|_| line != self.line);
+        self.line = line;
+
+        let breakpoint = entered.filter(|l| self.breakpoints.contains(l));
+        let stop = match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => path.len() <= depth,
+            Mode::Continue => false,
+        };
+        if !stop && breakpoint.is_none() {
+            return Ok(ExecOutcome::Done);
+        }
+
+        let mut out = String::new();
+        if let Some(line) = breakpoint {
+            writeln!(out, "breakpoint on line {line}.").unwrap();
+        }
+        out.push_str(&self.show(inst, path));
+
+        loop {
+            out.push_str("(bng) ");
+            let output = interpreter.output();
+            output
+                .write_all(out.as_bytes())
+                .and_then(|_| output.flush())
+                .map_err(|e| format!("the debugger failed to write output: {e}"))?;
+            out.clear();
+
+            let mut cmd = String::new();
+            let read = interpreter
+                .input()
+                .read_line(&mut cmd)
+                .map_err(|e| format!("the debugger failed to read a command: {e}"))?;
+            if read == 0 {
+                // nobody left to ask, so don't run on unsupervised
+                return Ok(ExecOutcome::Exit(0));
+            }
+
+            match self.command(&cmd, inst, path, interpreter.stack(), &mut out) {
+                Some(Resume::Run(mode)) => {
+                    self.mode = mode;
+                    self.repeat = Some(mode);
+                    return Ok(ExecOutcome::Done);
+                }
+                Some(Resume::Quit) => return Ok(ExecOutcome::Exit(0)),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Whether an instruction in `map` starts on `line` of `src`.
+fn has_line(map: &SourceMap, src: &str, line: usize) -> bool {
+    map.0
+        .iter()
+        .any(|(span, body)| span.line_col(src).0 == line || has_line(body, src, line))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn debug(src: &str, commands: &str) -> String {
+        let (instructions, map) = parse_spanned(src.into(), &ParseOptions::default()).unwrap();
+        let mut interpreter = Interpreter::with_io(commands.as_bytes(), Vec::new());
+        let mut debugger = Debugger::new(src, map);
+        interpreter
+            .execute_hooked(&instructions, &mut debugger)
+            .unwrap();
+        String::from_utf8(interpreter.into_io().1).unwrap()
+    }
+
+    #[test]
+    fn steps_and_breaks() {
+        let src = "2 1 <\n? ['x'P]\n5 P";
+
+        let out = debug(src, "n\nn\nn\np\nn\nn\nq\n");
+        assert!(out.starts_with("-> 1:1 [PushNum(2.0)]\n    2 1 <\n    ^\n(bng) -> 1:3"));
+        assert!(out.contains("-> 2:1 [IfStmt]\n    ? ['x'P]\n    ^\n"));
+        // stepped over the body, but it still ran
+        assert!(out.contains("(bng) [Bool(true)]\n(bng) x\n-> 3:1 [PushNum(5.0)]"));
+        assert!(!out.contains("[PushStr"));
+        assert!(!out.contains("5\n"));
+
+        let out = debug(src, "s\n\n\n\n\nb 3\nb 9\nc\nc\n");
+        assert!(out.contains("-> 2:4 [PushStr(\"x\")]\n    ? ['x'P]\n       ^^^\n"));
+        assert!(out.contains("no instruction on line 9."));
+        assert!(out
+            .ends_with("breakpoint on line 3.\n-> 3:1 [PushNum(5.0)]\n    5 P\n    ^\n(bng) 5\n"));
+    }
+}
@@ -1,3 +1,80 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Process exit codes used by the command line interface. A program can exit
+/// with its own code through syscall 0.
+pub const EXIT_RUNTIME: i32 = 1;
+pub const EXIT_PARSE: i32 = 2;
+pub const EXIT_IO: i32 = 3;
+pub const EXIT_LIMIT: i32 = 4;
+pub const EXIT_PERMISSION: i32 = 5;
+pub const EXIT_USAGE: i32 = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum BngError {
+    Parse(String),
+    Runtime(String),
+    Io(String),
+    /// The program went over one of its [`crate::Limits`].
+    Limit(String),
+    /// The program used a [`crate::sandbox::Capability`] it was denied.
+    Permission(String),
+}
+
+impl BngError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Parse(_) => EXIT_PARSE,
+            Self::Runtime(_) => EXIT_RUNTIME,
+            Self::Io(_) => EXIT_IO,
+            Self::Limit(_) => EXIT_LIMIT,
+            Self::Permission(_) => EXIT_PERMISSION,
+        }
+    }
+
+    /// Puts `context` in front of the message, keeping the kind of error.
+    pub fn context(self, context: &str) -> Self {
+        match self {
+            Self::Parse(e) => Self::Parse(format!("{context}: {e}")),
+            Self::Runtime(e) => Self::Runtime(format!("{context}: {e}")),
+            Self::Io(e) => Self::Io(format!("{context}: {e}")),
+            Self::Limit(e) => Self::Limit(format!("{context}: {e}")),
+            Self::Permission(e) => Self::Permission(format!("{context}: {e}")),
+        }
+    }
+}
+
+impl std::fmt::Display for BngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(e)
+            | Self::Runtime(e)
+            | Self::Io(e)
+            | Self::Limit(e)
+            | Self::Permission(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable ANSI colors in diagnostics and the interactive prompt.
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+pub fn color_enabled() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// Wrap `s` in the given ANSI color code, unless colors are disabled.
+pub fn paint(code: u8, s: &str) -> String {
+    if color_enabled() {
+        format!("\x1b[{code}m{s}\x1b[0m")
+    } else {
+        s.to_string()
+    }
+}
+
 #[macro_export]
 macro_rules! pflush {
     () => {
@@ -10,12 +87,12 @@ macro_rules! pflush {
 macro_rules! err {
     ($msg:expr) => {
         {
-            eprintln!("\x1b[91m[ERROR]\x1b[0m: {}", $msg);
+            eprintln!("{}: {}", $crate::errhandling::paint(91, "[ERROR]"), $msg);
         }
     };
     ($msg:expr, $($fmt_args:expr),*) => {
         {
-            eprintln!("\x1b[91m[ERROR]\x1b[0m: {}", format!($msg, $($fmt_args),*));
+            eprintln!("{}: {}", $crate::errhandling::paint(91, "[ERROR]"), format!($msg, $($fmt_args),*));
         }
     };
 }
@@ -24,14 +101,14 @@ macro_rules! err {
 macro_rules! assert_err {
     ($cond:expr, $msg:expr) => {
         if !($cond) {
-            eprintln!("\x1b[91m[ERROR]\x1b[0m: {}", $msg);
-            std::process::exit(1);
+            $crate::err!($msg);
+            std::process::exit($crate::errhandling::EXIT_RUNTIME);
         }
     };
     ($cond:expr, $msg:expr, $($fmt_args:expr),*) => {
         if !($cond) {
-            eprintln!("\x1b[91m[ERROR]\x1b[0m: {}", format!($msg, $($fmt_args),*));
-            std::process::exit(1);
+            $crate::err!($msg, $($fmt_args),*);
+            std::process::exit($crate::errhandling::EXIT_RUNTIME);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,79 @@
+//! Lets tools watch a program run on the tree walker. A [`Hook`] passed to
+//! [`Interpreter::execute_hooked`] is called around every instruction,
+//! including the ones in if and loop bodies.
+//!
+//! ```
+//! use bingbang::{hook::Hook, parse_bng, ExecOutcome, Instr, Interpreter};
+//!
+//! struct Count(usize);
+//!
+//! impl<R, W> Hook<R, W> for Count {
+//!     fn before(&mut self, _: &mut Interpreter<R, W>, _: &Instr, _: &[usize]) -> Result<ExecOutcome, String> {
+//!         self.0 += 1;
+//!         Ok(ExecOutcome::Done)
+//!     }
+//! }
+//!
+//! let instructions = parse_bng("3 (1 d)".into()).unwrap();
+//! let mut count = Count(0);
+//! Interpreter::new().execute_hooked(&instructions, &mut count).unwrap();
+//! assert_eq!(count.0, 8);
+//! ```
+
+use crate::parse::SourceMap;
+use crate::*;
+
+/// Where the instruction at `path` is, for tools to show: `line:col` when
+/// `map` has its span, or the path itself, like `#2.0.1`.
+pub fn location(map: &SourceMap, src: &str, path: &[usize]) -> String {
+    match map.at(path) {
+        Some(span) => {
+            let (line, col) = span.line_col(src);
+            format!("{line}:{col}")
+        }
+        None => {
+            let path = path.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+            format!("#{}", path.join("."))
+        }
+    }
+}
+
+/// `path` holds the index of the instruction in each enclosing body, ending
+/// with its own; [`parse::SourceMap::at`] turns it into a span.
+pub trait Hook<R, W> {
+    /// Called before `inst` runs. Returning [`ExecOutcome::Exit`] stops the
+    /// program as if it had exited.
+    fn before(
+        &mut self,
+        interpreter: &mut Interpreter<R, W>,
+        inst: &Instr,
+        path: &[usize],
+    ) -> Result<ExecOutcome, String> {
+        let _ = (interpreter, inst, path);
+        Ok(ExecOutcome::Done)
+    }
+
+    /// Called after `inst` ran without an error, after its whole body for
+    /// ifs and loops.
+    fn after(
+        &mut self,
+        interpreter: &mut Interpreter<R, W>,
+        inst: &Instr,
+        path: &[usize],
+    ) -> Result<(), String> {
+        let _ = (interpreter, inst, path);
+        Ok(())
+    }
+
+    /// Called instead of [`Hook::after`] when `inst` fails with `error`. An
+    /// if or loop whose body failed isn't called again.
+    fn error(
+        &mut self,
+        interpreter: &mut Interpreter<R, W>,
+        inst: &Instr,
+        path: &[usize],
+        error: &str,
+    ) {
+        let _ = (interpreter, inst, path, error);
+    }
+}
@@ -0,0 +1,29 @@
+use std::fmt;
+
+use crate::*;
+
+/// A Rust function callable from bingbang with `:name`. It gets the whole
+/// stack, with at least `arity` values on it.
+pub type HostFn = Box<dyn FnMut(&mut Stack) -> Result<(), BngError>>;
+
+pub struct HostFunction {
+    pub arity: usize,
+    pub(crate) func: HostFn,
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns whether `name` can be called with `:name`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_name_char)
+}
+
+pub(crate) fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
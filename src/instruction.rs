@@ -26,7 +26,7 @@ pub const INTERACTIVE_COMMENT: char = ';';
 
 type Body = Vec<Instr>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instr {
     Print,
     PrintLn,
@@ -46,7 +46,8 @@ pub enum Instr {
     FlipStack,
 
     FnDef,
-    FnCall,
+    /// Call a function registered by the host.
+    FnCall(String),
 
     Loop(Body),
 
@@ -56,10 +57,22 @@ pub enum Instr {
 
     PushStr(String),
     PushNum(f32),
+    /// Push a Bool. There is no syntax for it; the optimizer leaves it
+    /// behind when it folds a comparison.
+    PushBool(bool),
 
     Syscall,
     Time,
     TimeFmt,
+    /// The date and time instructions, see [`crate::time`].
+    Now,
+    Epoch,
+    EpochMillis,
+    ParseTime,
+    FormatTime,
+    AddTime,
+    TimeDiff,
+    InZone,
 
     IfStmt(Body),
 
@@ -71,3 +84,60 @@ pub enum Instr {
     ShowStack,
     Exit,
 }
+
+impl Instr {
+    /// The name of the instruction's variant, without its payload.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instr::Print => "Print",
+            Instr::PrintLn => "PrintLn",
+            Instr::Read => "Read",
+            Instr::Div => "Div",
+            Instr::Plus => "Plus",
+            Instr::Sub => "Sub",
+            Instr::Mul => "Mul",
+            Instr::GreaterThan => "GreaterThan",
+            Instr::LessThan => "LessThan",
+            Instr::Sum => "Sum",
+            Instr::ParseNum => "ParseNum",
+            Instr::Reverse => "Reverse",
+            Instr::GenRange => "GenRange",
+            Instr::FlipStack => "FlipStack",
+            Instr::FnDef => "FnDef",
+            Instr::FnCall(_) => "FnCall",
+            Instr::Loop(_) => "Loop",
+            Instr::Pop => "Pop",
+            Instr::ClearScreen => "ClearScreen",
+            Instr::PushStr(_) => "PushStr",
+            Instr::PushNum(_) => "PushNum",
+            Instr::PushBool(_) => "PushBool",
+            Instr::Syscall => "Syscall",
+            Instr::Time => "Time",
+            Instr::TimeFmt => "TimeFmt",
+            Instr::Now => "Now",
+            Instr::Epoch => "Epoch",
+            Instr::EpochMillis => "EpochMillis",
+            Instr::ParseTime => "ParseTime",
+            Instr::FormatTime => "FormatTime",
+            Instr::AddTime => "AddTime",
+            Instr::TimeDiff => "TimeDiff",
+            Instr::InZone => "InZone",
+            Instr::IfStmt(_) => "IfStmt",
+            Instr::Eq => "Eq",
+            Instr::Not => "Not",
+            Instr::ClearStack => "ClearStack",
+            Instr::PrintStack => "PrintStack",
+            Instr::PrintStackLn => "PrintStackLn",
+            Instr::ShowStack => "ShowStack",
+            Instr::Exit => "Exit",
+        }
+    }
+
+    /// Like `{:?}`, but leaves out the bodies of ifs and loops.
+    pub fn brief(&self) -> String {
+        match self {
+            Instr::IfStmt(_) | Instr::Loop(_) => self.name().into(),
+            _ => format!("{self:?}"),
+        }
+    }
+}
@@ -1,38 +1,269 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Read, StdinLock, Stdout, Write};
+
+use crate::clock::Clock;
+use crate::hook::Hook;
+use crate::host::{is_valid_name, HostFunction};
+use crate::limits::Usage;
+use crate::sandbox::Capability;
 use crate::*;
 
-pub fn interpret_instructions(
-    instructions: &Vec<Instr>,
-    stack: &mut Vec<StackVal>,
-) -> Result<(), String> {
-    for (idx, inst) in instructions.iter().enumerate() {
+/// Holds the stack between runs, so a host can push arguments, run some
+/// code and pop the results.
+///
+/// `i` reads lines from `R`, everything else that prints writes to `W`.
+pub struct Interpreter<R = StdinLock<'static>, W = Stdout> {
+    pub(crate) stack: Stack,
+    host_fns: HashMap<String, HostFunction>,
+    pub(crate) limits: Limits,
+    pub(crate) usage: Usage,
+    pub(crate) denied: Vec<Capability>,
+    pub(crate) clock: Clock,
+    warn: Box<dyn FnMut(&str)>,
+    input: R,
+    output: W,
+}
+
+impl Interpreter {
+    /// An interpreter reading from stdin and writing to stdout.
+    ///
+    /// It holds the stdin lock for as long as it lives, so any other read
+    /// from stdin, on any thread, blocks until it is dropped. Read through
+    /// [`Self::input`] instead, or give it other input with
+    /// [`Self::with_io`].
+    pub fn new() -> Self {
+        Self::with_io(io::stdin().lock(), io::stdout())
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, W> fmt::Debug for Interpreter<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interpreter")
+            .field("stack", &self.stack)
+            .field("host_fns", &self.host_fns)
+            .field("limits", &self.limits)
+            .field("denied", &self.denied)
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    /// An interpreter reading input from `input` and writing to `output`.
+    ///
+    /// ```
+    /// use bingbang::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::with_io("world\n".as_bytes(), Vec::new());
+    /// interpreter.run("'hello 'p iP").unwrap();
+    /// assert_eq!(interpreter.output(), b"hello world\n");
+    /// ```
+    pub fn with_io(input: R, output: W) -> Self {
+        Self {
+            stack: Stack::new(),
+            host_fns: HashMap::new(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            denied: Vec::new(),
+            clock: Clock::default(),
+            warn: Box::new(|msg| err!(msg)),
+            input,
+            output,
+        }
+    }
+
+    pub fn input(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    pub fn output(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    pub fn into_io(self) -> (R, W) {
+        (self.input, self.output)
+    }
+
+    /// Sends warnings, which don't stop the program, to `func` instead of
+    /// printing them to stderr.
+    ///
+    /// ```
+    /// use std::{cell::RefCell, rc::Rc};
+    /// use bingbang::Interpreter;
+    ///
+    /// let warnings = Rc::new(RefCell::new(Vec::new()));
+    /// let sink = warnings.clone();
+    /// let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+    /// interpreter.on_warning(move |msg| sink.borrow_mut().push(msg.to_string()));
+    /// interpreter.run("1 'a' 2 s P").unwrap();
+    /// assert_eq!(interpreter.output(), b"3\n");
+    /// assert_eq!(warnings.borrow().len(), 1);
+    /// ```
+    pub fn on_warning<F>(&mut self, func: F)
+    where
+        F: FnMut(&str) + 'static,
+    {
+        self.warn = Box::new(func);
+    }
+
+    /// Parses and runs `src` on the current stack.
+    pub fn run(&mut self, src: &str) -> Result<ExecOutcome, BngError> {
+        let instructions = parse_bng(src.into()).map_err(BngError::Parse)?;
+        self.execute(&instructions)
+    }
+
+    /// Runs already parsed instructions on the current stack.
+    pub fn execute(&mut self, instructions: &[Instr]) -> Result<ExecOutcome, BngError> {
+        self.start_run();
+        self.interpret_instructions(instructions)
+            .map_err(|e| self.run_error(e))
+    }
+
+    /// Like [`Self::execute`], calling `hook` around every instruction.
+    pub fn execute_hooked(
+        &mut self,
+        instructions: &[Instr],
+        hook: &mut dyn Hook<R, W>,
+    ) -> Result<ExecOutcome, BngError> {
+        self.start_run();
+        self.walk(instructions, &mut Vec::new(), hook)
+            .map_err(|e| self.run_error(e))
+    }
+
+    pub fn stack(&self) -> &[StackVal] {
+        &self.stack
+    }
+
+    pub fn stack_mut(&mut self) -> &mut Vec<StackVal> {
+        &mut self.stack
+    }
+
+    pub fn push(&mut self, val: StackVal) {
+        self.stack.push(val);
+    }
+
+    pub fn pop(&mut self) -> Option<StackVal> {
+        self.stack.pop()
+    }
+
+    /// Makes `func` callable as `:name`. Calls fail before reaching `func`
+    /// when fewer than `arity` values are on the stack.
+    ///
+    /// ```
+    /// use bingbang::{Interpreter, StackVal};
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.register("double", 1, |stack| {
+    ///     if let Some(StackVal::Number(n)) = stack.pop() {
+    ///         stack.push(StackVal::Number(n * 2.0));
+    ///     }
+    ///     Ok(())
+    /// });
+    /// interpreter.run("21 :double").unwrap();
+    /// assert_eq!(interpreter.pop(), Some(StackVal::Number(42.0)));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `name` contains anything but ascii letters, digits and `_`.
+    pub fn register<F>(&mut self, name: &str, arity: usize, func: F)
+    where
+        F: FnMut(&mut Stack) -> Result<(), BngError> + 'static,
+    {
+        assert!(is_valid_name(name), "invalid host function name `{name}`");
+        self.host_fns.insert(
+            name.to_string(),
+            HostFunction {
+                arity,
+                func: Box::new(func),
+            },
+        );
+    }
+}
+
+/// Applies `+`, `-`, `*` or `/` to `operands`; `None` for any other
+/// instruction.
+pub fn perform_math_op(operands: (f32, f32), operation: &Instr) -> Option<StackVal> {
+    let n = match operation {
+        Instr::Plus => operands.0 + operands.1,
+        Instr::Div => operands.0 / operands.1,
+        Instr::Sub => operands.0 - operands.1,
+        Instr::Mul => operands.0 * operands.1,
+        _ => return None,
+    };
+    Some(StackVal::Number(n))
+}
+
+/// How a sequence of instructions finished.
+#[derive(Debug, PartialEq)]
+pub enum ExecOutcome {
+    /// Ran off the end of the instructions.
+    Done,
+    /// The program asked to exit with the given code (`q` or syscall 0).
+    Exit(i32),
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    pub(crate) fn interpret_instructions(
+        &mut self,
+        instructions: &[Instr],
+    ) -> Result<ExecOutcome, String> {
+        let limited = self.limited();
+        for (idx, inst) in instructions.iter().enumerate() {
+            if limited {
+                self.tick()?;
+            }
+            if let ExecOutcome::Exit(code) = self.step(inst, idx)? {
+                return Ok(ExecOutcome::Exit(code));
+            }
+            if limited {
+                self.check_stack(idx)?;
+            }
+        }
+        Ok(ExecOutcome::Done)
+    }
+
+    /// Performs a single instruction; `idx` is only used in error messages.
+    pub(crate) fn step(&mut self, inst: &Instr, idx: usize) -> Result<ExecOutcome, String> {
         match inst {
-            Instr::PushStr(s) => stack.push(StackVal::String(s.into())),
-            Instr::PushNum(i) => stack.push(StackVal::Number(*i)),
+            Instr::PushStr(s) => self.stack.push(StackVal::String(s.into())),
+            Instr::PushNum(i) => self.stack.push(StackVal::Number(*i)),
+            Instr::PushBool(b) => self.stack.push(StackVal::Bool(*b)),
 
             Instr::Print => {
-                if stack.len() < 1 {
+                if self.stack.is_empty() {
                     return Err(format!(
                         "while performing instruction [{inst:?}] at index {}, stack empty.",
                         idx
                     ));
                 }
 
-                print!("{last}", last = stack.pop().unwrap());
-                pflush!();
+                let last = self.stack.pop().unwrap();
+                write!(self.output, "{last}")
+                    .and_then(|_| self.output.flush())
+                    .map_err(|e| write_err(inst, idx, e))?;
             }
             Instr::PrintLn => {
-                if stack.len() < 1 {
+                if self.stack.is_empty() {
                     return Err(format!(
                         "while performing instruction [{inst:?}] at index {}, stack empty.",
                         idx
                     ));
                 }
 
-                println!("{last}", last = stack.pop().unwrap());
+                let last = self.stack.pop().unwrap();
+                writeln!(self.output, "{last}").map_err(|e| write_err(inst, idx, e))?;
             }
 
             Instr::Plus | Instr::Div | Instr::Sub | Instr::Mul => {
-                if stack.len() < 2 {
+                if self.stack.len() < 2 {
                     return Err(format!(
                         "while performing [{:?}] at index {}, stack length too short. (must be >= 2)",
                         inst,
@@ -40,29 +271,26 @@ pub fn interpret_instructions(
                     ));
                 }
 
-                let vals: Vec<Result<f32, String>> = vec![stack.pop().unwrap(), stack.pop().unwrap()].iter().map(|v| {
+                let vals: Vec<Result<f32, String>> = [self.stack.pop().unwrap(), self.stack.pop().unwrap()].iter().map(|v| {
                     match v {
                         StackVal::Number(n) => Ok(*n),
                         _ => Err(format!("while performing [{:?}] at index {}, expected type Number, but got type {}.", inst, idx, v))
                     }
                 }).collect();
 
-                let oper1;
-                let oper2;
-
-                match &vals[0] {
-                    Ok(n) => oper1 = n,
+                let oper1 = match &vals[0] {
+                    Ok(n) => n,
                     Err(e) => return Err(e.clone()),
-                }
-                match &vals[1] {
-                    Ok(n) => oper2 = n,
+                };
+                let oper2 = match &vals[1] {
+                    Ok(n) => n,
                     Err(e) => return Err(e.clone()),
-                }
+                };
 
-                stack.push(perform_math_op((*oper1, *oper2), inst))
+                self.stack.push(perform_math_op((*oper1, *oper2), inst).unwrap())
             }
             Instr::Sum => {
-                if stack.len() < 1 {
+                if self.stack.is_empty() {
                     return Err(format!(
                         "while performing [{:?}] at index {}, failed to SUM because stack is empty.",
                         inst,
@@ -72,46 +300,56 @@ pub fn interpret_instructions(
 
                 let mut nums: Vec<f32> = Vec::new();
 
-                while stack.len() >= 1 {
-                    if let StackVal::Number(n) = stack.pop().unwrap() {
+                while !self.stack.is_empty() {
+                    if let StackVal::Number(n) = self.stack.pop().unwrap() {
                         nums.push(n);
                     } else {
-                        err!(
+                        (self.warn)(&format!(
                             "while performing [{:?}] at index {}, failed to SUM because non-number is in stack.",
                             inst, idx
-                        )
+                        ))
                     }
                 }
 
-                for s in stack.iter() {
+                for s in self.stack.iter() {
                     if let StackVal::Number(n) = s {
                         nums.push(*n);
                     } else {
-                        err!(
+                        (self.warn)(&format!(
                             "while performing [{:?}] at index {}, failed to SUM because non-number is in stack.",
                             inst, idx
-                        )
+                        ))
                     }
                 }
 
                 let sum = nums.iter().fold(0.0, |acc: f32, n| acc + n);
-                stack.push(StackVal::Number(sum));
+                self.stack.push(StackVal::Number(sum));
             }
             Instr::Read => {
+                self.need(Capability::Stdin, inst, idx)?;
                 let mut buf = String::new();
-                std::io::stdin().read_line(&mut buf).unwrap();
-                stack.push(StackVal::String(
-                    buf.split_once('\n').unwrap().0.to_string(),
-                ));
+                let max = self.max_line();
+                let read = (&mut self.input).take(max).read_line(&mut buf).map_err(|e| {
+                    format!("while performing [{inst:?}] at index {idx}, failed to read stdin: {e}")
+                })?;
+                if read == 0 {
+                    return Err(format!(
+                        "while performing [{inst:?}] at index {idx}, reached end of input."
+                    ));
+                }
+                // the last line of piped input may not end in a newline
+                let line = buf.strip_suffix('\n').unwrap_or(&buf);
+                let line = line.strip_suffix('\r').unwrap_or(line);
+                self.stack.push(StackVal::String(line.to_string()));
             }
             Instr::ParseNum => {
-                if stack.len() < 1 {
+                if self.stack.is_empty() {
                     return Err(format!(
                         "while trying to [{:?}] at index {}, failed because stack is empty.",
                         inst, idx
                     ));
                 }
-                let parsed = match stack.pop().unwrap() {
+                let parsed = match self.stack.pop().unwrap() {
                     StackVal::Number(n) => n,
                     StackVal::String(s) => {
                         let p = s.trim().parse::<f32>();
@@ -124,11 +362,11 @@ pub fn interpret_instructions(
                     }
                     StackVal::Bool(b) => (b as i8) as f32,
                 };
-                stack.push(StackVal::Number(parsed));
+                self.stack.push(StackVal::Number(parsed));
             }
 
             Instr::Eq => {
-                let l = stack.len();
+                let l = self.stack.len();
                 if l < 2 {
                     return Err(format!(
                         "while performing [{:?}] at index {}, expected stack length to be >= 2, but got {}.",
@@ -136,46 +374,49 @@ pub fn interpret_instructions(
                     ));
                 }
 
-                let elem1 = stack.pop().unwrap();
-                let elem2 = stack.pop().unwrap();
+                let elem1 = self.stack.pop().unwrap();
+                let elem2 = self.stack.pop().unwrap();
 
-                stack.push(StackVal::Bool(elem1 == elem2));
+                self.stack.push(StackVal::Bool(elem1 == elem2));
             }
 
-            Instr::Exit => std::process::exit(0),
-            Instr::ClearStack => stack.clear(),
+            Instr::Exit => {
+                self.need(Capability::Exit, inst, idx)?;
+                return Ok(ExecOutcome::Exit(0));
+            }
+            Instr::ClearStack => self.stack.clear(),
             Instr::PrintStack => {
-                if stack.len() == 0 {
-                    continue;
+                if self.stack.is_empty() {
+                    return Ok(ExecOutcome::Done);
                 }
-                while stack.len() > 1 {
-                    print!("{}", stack.pop().unwrap());
-                    pflush!();
+                while self.stack.len() > 1 {
+                    let last = self.stack.pop().unwrap();
+                    write!(self.output, "{last}").map_err(|e| write_err(inst, idx, e))?;
                 }
-                println!("");
+                writeln!(self.output).map_err(|e| write_err(inst, idx, e))?;
             }
             Instr::PrintStackLn => {
-                if stack.len() == 0 {
-                    continue;
+                if self.stack.is_empty() {
+                    return Ok(ExecOutcome::Done);
                 }
-                while stack.len() != 0 {
-                    println!("{}", stack.pop().unwrap());
+                while let Some(last) = self.stack.pop() {
+                    writeln!(self.output, "{last}").map_err(|e| write_err(inst, idx, e))?;
                 }
             }
             Instr::ShowStack => {
-                for s in stack.iter() {
-                    println!("{s}");
+                for s in self.stack.iter() {
+                    writeln!(self.output, "{s}").map_err(|e| write_err(inst, idx, e))?;
                 }
             }
             Instr::Syscall => {
-                if stack.len() < 2 {
+                if self.stack.len() < 2 {
                     return Err(format!(
                         "while performing [{:?}] at index {}, stack must contain 2 values.",
                         inst, idx
                     ));
                 }
 
-                let oper1 = match stack.pop().unwrap() {
+                let oper1 = match self.stack.pop().unwrap() {
                     StackVal::Number(n) => n,
                     _ => {
                         return Err(format!(
@@ -184,13 +425,14 @@ pub fn interpret_instructions(
                         ))
                     }
                 };
-                let oper2 = stack.pop().unwrap();
+                let oper2 = self.stack.pop().unwrap();
 
                 match oper1 as i32 {
                     0 => {
                         // exit
+                        self.need(Capability::Exit, inst, idx)?;
                         if let StackVal::Number(n) = oper2 {
-                            std::process::exit(n as i32);
+                            return Ok(ExecOutcome::Exit(n as i32));
                         } else {
                             return Err(format!("for syscall argument 0, got invalid type. Expected Number, got {:?}", oper2));
                         }
@@ -204,21 +446,25 @@ pub fn interpret_instructions(
                 }
             }
             Instr::Time => {
-                let time = chrono::Local::now().format("%d-%m-%Y");
-                stack.push(StackVal::String(time.to_string()));
+                self.need(Capability::Clock, inst, idx)?;
+                let time = self.clock.now().format("%d-%m-%Y");
+                self.stack.push(StackVal::String(time.to_string()));
             }
             Instr::TimeFmt => {
-                if stack.len() < 1 {
+                self.need(Capability::Clock, inst, idx)?;
+                if self.stack.is_empty() {
                     return Err(format!(
                         "while performing [{:?}] at index {}, stack is empty.",
                         inst, idx
                     ));
                 }
-                let last = stack.pop().unwrap();
+                let last = self.stack.pop().unwrap();
 
                 let to_push = {
                     if let StackVal::String(fmt) = last {
-                        chrono::Local::now().format(&fmt).to_string()
+                        let fail = |e| format!("while performing [{:?}] at index {}, {}", inst, idx, e);
+                        time::check_format(&fmt).map_err(fail)?;
+                        time::format(&self.clock.now(), &fmt).map_err(fail)?
                     } else {
                         return Err(format!(
                             "while trying to perform [{:?}] at index {}, expected type String, but got {:?}.",
@@ -227,19 +473,27 @@ pub fn interpret_instructions(
                     }
                 };
 
-                stack.push(StackVal::String(to_push));
+                self.stack.push(StackVal::String(to_push));
             },
+            Instr::Now
+            | Instr::Epoch
+            | Instr::EpochMillis
+            | Instr::ParseTime
+            | Instr::FormatTime
+            | Instr::AddTime
+            | Instr::TimeDiff
+            | Instr::InZone => self.time_step(inst, idx)?,
             Instr::Not => {
-                if stack.len() < 1 {
+                if self.stack.is_empty() {
                     return Err(format!(
                         "while performing [{:?}] at index {}, stack is empty.",
                         inst, idx
                     ))
                 }
-                let last = stack.pop().unwrap();
+                let last = self.stack.pop().unwrap();
 
                 if let StackVal::Bool(b) = last {
-                    stack.push(StackVal::Bool(!b));
+                    self.stack.push(StackVal::Bool(!b));
                 } else {
                     return Err(
                         format!("while trying to perform operation [{:?}], expected Bool(), got {:?}", inst, last)
@@ -247,39 +501,20 @@ pub fn interpret_instructions(
                 }
             }
             Instr::IfStmt(body) => {
-                if stack.len() < 1 {
-                    return Err(format!(
-                        "while performing [{:?}] at index {}, stack is empty.",
-                        inst, idx
-                    ));
-                }
-                let last = stack.pop().unwrap();
-                let cond = if let StackVal::Bool(b) = last {
-                    b
-                } else {
-                    return Err(format!(
-                        "while performing [{:?}] at index {}, expected Bool() on stack, got {:?}",
-                        inst, idx, last
-                    ));
-                };
-
-                if cond {
-                    match interpret_instructions(body, stack) {
-                        Ok(_) => {},
-                        Err(e) => return Err(e)
-                    }
+                if self.condition(inst, idx)? {
+                    return self.body(body, 1, idx);
                 }
             },
             Instr::GreaterThan | Instr::LessThan => {
-                if stack.len() < 2 {
+                if self.stack.len() < 2 {
                     return Err(format!(
                         "while performing [{:?}] at index {}, not enough elements in stack.",
                         inst, idx
                     ))
                 }
 
-                let elem1 = stack.pop().unwrap();
-                let elem2 = stack.pop().unwrap();
+                let elem1 = self.stack.pop().unwrap();
+                let elem2 = self.stack.pop().unwrap();
 
                 let oper1;
                 let oper2;
@@ -301,33 +536,33 @@ pub fn interpret_instructions(
                 } else if *inst == Instr::LessThan {
                     StackVal::Bool(oper1 < oper2)
                 } else {unimplemented!()};
-                stack.push(to_push);
+                self.stack.push(to_push);
             },
             Instr::Reverse => {
-                if stack.len() < 1 {
+                if self.stack.is_empty() {
                     return Err(format!("while trying to perform [{inst:?}] at index {idx}, stack is empty."))
                 }
-                let last = stack.pop().unwrap();
+                let last = self.stack.pop().unwrap();
                 if let StackVal::String(s) = last {
-                    stack.push(StackVal::String(s.chars().rev().collect()))
+                    self.stack.push(StackVal::String(s.chars().rev().collect()))
                 } else {
                     return Err(format!("while trying to perform [{inst:?}] at index {idx}, expected String(), got {inst:?}"))
                 }
             },
             Instr::Pop => {
-                if stack.len() >= 1 {
-                    stack.pop();
+                if !self.stack.is_empty() {
+                    self.stack.pop();
                 }
             },
             Instr::GenRange => {
-                if stack.len() < 2 {
+                if self.stack.len() < 2 {
                     return Err(format!(
                         "while trying to perform [{inst:?}] at index {idx}, stack length too short."
                     ))
                 }
 
-                let elem1 = stack.pop().unwrap();
-                let elem2 = stack.pop().unwrap();
+                let elem1 = self.stack.pop().unwrap();
+                let elem2 = self.stack.pop().unwrap();
 
                 let oper1;
                 let oper2;
@@ -343,23 +578,163 @@ pub fn interpret_instructions(
                     return Err(format!("failed to [{inst:?}]: Not a Number: {elem2:?}"))
                 }
 
+                let len = (oper2 as i64 - oper1 as i64).max(0) as usize;
+                self.make_room(len, idx)?;
+                let limited = self.limited();
                 for i in oper1..oper2 {
-                    stack.push(StackVal::Number(i as f32));
+                    // every value costs fuel, so a huge range still times out
+                    if limited {
+                        self.tick()?;
+                    }
+                    self.stack.push(StackVal::Number(i as f32));
                 }
             },
             Instr::FlipStack => {
-                stack.reverse();
+                self.stack.reverse();
             },
             Instr::FnDef => {},
-            Instr::FnCall => {},
+            Instr::FnCall(name) => {
+                let host_fn = match self.host_fns.get_mut(name) {
+                    Some(f) => f,
+                    None => {
+                        return Err(format!(
+                            "while performing [{inst:?}] at index {idx}, no function named `{name}`."
+                        ))
+                    }
+                };
+                if self.stack.len() < host_fn.arity {
+                    return Err(format!(
+                        "while performing [{inst:?}] at index {idx}, `{name}` takes {} values, but the stack has {}.",
+                        host_fn.arity,
+                        self.stack.len()
+                    ));
+                }
+                (host_fn.func)(&mut self.stack)
+                    .map_err(|e| format!("while performing [{inst:?}] at index {idx}, {e}"))?;
+            },
             Instr::ClearScreen => {
-                print!("\x1b[2J\x1b[H");
-                pflush!();
+                self.need(Capability::Terminal, inst, idx)?;
+                write!(self.output, "\x1b[2J\x1b[H")
+                    .and_then(|_| self.output.flush())
+                    .map_err(|e| write_err(inst, idx, e))?;
             },
             Instr::Loop(body) => {
-                
+                let count = self.count(inst, idx)?;
+                return self.body(body, count, idx);
             }
         }
+        Ok(ExecOutcome::Done)
+    }
+
+    /// Runs `body` of the if or loop at `idx` `times` times.
+    fn body(&mut self, body: &[Instr], times: i64, idx: usize) -> Result<ExecOutcome, String> {
+        if times <= 0 {
+            return Ok(ExecOutcome::Done);
+        }
+        self.enter(idx)?;
+        for _ in 0..times {
+            if let ExecOutcome::Exit(code) = self.interpret_instructions(body)? {
+                return Ok(ExecOutcome::Exit(code));
+            }
+        }
+        self.leave();
+        Ok(ExecOutcome::Done)
+    }
+
+    /// Pops the condition of an if.
+    fn condition(&mut self, inst: &Instr, idx: usize) -> Result<bool, String> {
+        match self.stack.pop() {
+            Some(StackVal::Bool(b)) => Ok(b),
+            Some(v) => Err(format!(
+                "while performing [{:?}] at index {}, expected Bool() on stack, got {:?}",
+                inst, idx, v
+            )),
+            None => Err(format!(
+                "while performing [{:?}] at index {}, stack is empty.",
+                inst, idx
+            )),
+        }
     }
-    Ok(())
+
+    /// Pops the number of iterations of a loop.
+    fn count(&mut self, inst: &Instr, idx: usize) -> Result<i64, String> {
+        match self.stack.pop() {
+            Some(StackVal::Number(n)) => Ok(n as i64),
+            Some(v) => Err(format!(
+                "while performing [{inst:?}] at index {idx}, expected a Number of iterations, got {v:?}."
+            )),
+            None => Err(format!(
+                "while performing [{inst:?}] at index {idx}, stack is empty."
+            )),
+        }
+    }
+
+    /// Like [`Self::interpret_instructions`], with `hook` called around each
+    /// instruction.
+    fn walk(
+        &mut self,
+        instructions: &[Instr],
+        path: &mut Vec<usize>,
+        hook: &mut dyn Hook<R, W>,
+    ) -> Result<ExecOutcome, String> {
+        for (idx, inst) in instructions.iter().enumerate() {
+            path.push(idx);
+            let outcome = self.walk_one(inst, idx, path, hook);
+            path.pop();
+            if let ExecOutcome::Exit(code) = outcome? {
+                return Ok(ExecOutcome::Exit(code));
+            }
+        }
+        Ok(ExecOutcome::Done)
+    }
+
+    fn walk_one(
+        &mut self,
+        inst: &Instr,
+        idx: usize,
+        path: &mut Vec<usize>,
+        hook: &mut dyn Hook<R, W>,
+    ) -> Result<ExecOutcome, String> {
+        self.tick()?;
+        if let ExecOutcome::Exit(code) = hook.before(self, inst, path)? {
+            return Ok(ExecOutcome::Exit(code));
+        }
+        let result = match inst {
+            // an if runs its body at most once
+            Instr::IfStmt(body) => self
+                .condition(inst, idx)
+                .map(|cond| (&body[..], i64::from(cond), ExecOutcome::Done)),
+            Instr::Loop(body) => self
+                .count(inst, idx)
+                .map(|count| (&body[..], count, ExecOutcome::Done)),
+            _ => self.step(inst, idx).map(|outcome| (&[][..], 0, outcome)),
+        };
+        let result = result.and_then(|v| self.check_stack(idx).map(|_| v));
+        let (body, times, mut outcome) = match result {
+            Ok(v) => v,
+            Err(e) => {
+                hook.error(self, inst, path, &e);
+                return Err(e);
+            }
+        };
+
+        if times > 0 {
+            self.enter(idx)?;
+        }
+        for _ in 0..times {
+            outcome = self.walk(body, path, hook)?;
+            if outcome != ExecOutcome::Done {
+                return Ok(outcome);
+            }
+        }
+        if times > 0 {
+            self.leave();
+        }
+        hook.after(self, inst, path)?;
+        Ok(outcome)
+    }
+}
+
+fn write_err(inst: &Instr, idx: usize, e: io::Error) -> String {
+    format!("while performing [{inst:?}] at index {idx}, failed to write output: {e}")
 }
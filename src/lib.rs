@@ -0,0 +1,502 @@
+//! *bingbang* is a stack-based esoteric programming language.
+//!
+//! Programs are parsed into [`Instr`]uctions by [`parse_bng`] and run on a
+//! stack of [`StackVal`]s by an [`Interpreter`]:
+//!
+//! ```
+//! use bingbang::{Interpreter, StackVal};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.push(StackVal::Number(2.0));
+//! interpreter.run("3 *").unwrap();
+//! assert_eq!(interpreter.pop(), Some(StackVal::Number(6.0)));
+//! ```
+
+pub mod bngc;
+pub mod bytecode;
+pub mod check;
+pub mod clock;
+pub mod compile;
+pub mod debug;
+pub mod errhandling;
+pub mod hook;
+pub mod host;
+pub mod instruction;
+pub mod interpret;
+pub mod limits;
+pub mod optimize;
+pub mod parse;
+pub mod profile;
+pub mod sandbox;
+pub mod stackval;
+pub mod time;
+pub mod trace;
+pub mod vm;
+
+use instruction::*;
+
+pub use errhandling::BngError;
+pub use instruction::Instr;
+pub use interpret::{perform_math_op, ExecOutcome, Interpreter};
+pub use limits::Limits;
+pub use parse::{parse_bng, parse_bng_with, parse_spanned, ParseOptions};
+pub use stackval::{Stack, StackVal};
+
+#[cfg(test)]
+mod test {
+    use crate::bytecode::Chunk;
+    use crate::optimize;
+    use crate::parse;
+    use crate::{parse_bng, parse_bng_with, ParseOptions, Instr, compile::{compile, compile_to, Target}, perform_math_op, ExecOutcome, Interpreter, StackVal, BngError};
+    #[test]
+    fn hello_world() {
+        let parsed = parse_bng("'Hello World'P".into()).unwrap();
+        assert!(
+            parsed
+                == vec![
+                    Instr::PushStr("Hello World".into()),
+                    Instr::PrintLn,
+                ]
+        );
+    }
+    #[test]
+    fn basic_math() {
+        let parsed = parse_bng("1 2 3 + P".into()).unwrap();
+        assert!(
+            parsed
+                == vec![
+                    Instr::PushNum(1.0),
+                    Instr::PushNum(2.0),
+                    Instr::PushNum(3.0),
+                    Instr::Plus,
+                    Instr::PrintLn,
+                ]
+        );
+    }
+
+    #[test]
+    fn math_ops() {
+        assert_eq!(perform_math_op((6.0, 3.0), &Instr::Div), Some(StackVal::Number(2.0)));
+        assert_eq!(perform_math_op((6.0, 3.0), &Instr::Sub), Some(StackVal::Number(3.0)));
+        assert_eq!(perform_math_op((6.0, 3.0), &Instr::Eq), None);
+    }
+
+    #[test]
+    fn parse_numbers() {
+        let parsed = parse_bng("1 '1'n + P".into()).unwrap();
+        assert!(
+            parsed
+                == vec![
+                    Instr::PushNum(1.0),
+                    Instr::PushStr("1".into()),
+                    Instr::ParseNum,
+                    Instr::Plus,
+                    Instr::PrintLn,
+                ]
+        );
+    }
+
+    #[test]
+    fn caps_nesting() {
+        let nest = |depth| format!("{}'deep'P{}", "1 1 = ? [1 (".repeat(depth / 2), ")]".repeat(depth / 2));
+        let deepest = nest(parse::MAX_DEPTH);
+        let deeper = format!("\n{}", nest(parse::MAX_DEPTH + 2));
+        // unoptimized builds take a few KB of stack per level, more than a
+        // test thread has
+        let parsed = std::thread::Builder::new()
+            .stack_size(32 << 20)
+            .spawn(|| (parse_bng(deepest).is_ok(), parse_bng(deeper)))
+            .unwrap();
+        assert_eq!(
+            parsed.join().unwrap(),
+            (true, Err("line 2; ifs and loops are nested more than 1000 deep.".into()))
+        );
+    }
+
+    #[test]
+    fn implicit_exit() {
+        let opts = ParseOptions { implicit_exit: true };
+        let parsed = parse_bng_with("1 1 = ? ['x'P]".into(), &opts).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                Instr::PushNum(1.0),
+                Instr::PushNum(1.0),
+                Instr::Eq,
+                Instr::IfStmt(vec![Instr::PushStr("x".into()), Instr::PrintLn]),
+                Instr::Exit,
+            ]
+        );
+    }
+
+    #[test]
+    fn exit_from_if_body() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.run("1 1 = ? [7 0 $] 'unreachable'P"),
+            Ok(ExecOutcome::Exit(7))
+        );
+        assert!(interpreter.stack().is_empty());
+    }
+
+    #[test]
+    fn interpreter_keeps_stack() {
+        let mut interpreter = Interpreter::new();
+        interpreter.run("1 2").unwrap();
+        interpreter.push(StackVal::String("x".into()));
+        assert_eq!(interpreter.stack().len(), 3);
+
+        assert_eq!(interpreter.run("d +"), Ok(ExecOutcome::Done));
+        assert_eq!(interpreter.stack(), &[StackVal::Number(3.0)]);
+
+        assert!(matches!(interpreter.run("'"), Err(BngError::Parse(_))));
+        assert!(matches!(interpreter.run("'a' +"), Err(BngError::Runtime(_))));
+    }
+
+    #[test]
+    fn sum_warnings() {
+        let warnings = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = warnings.clone();
+        let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+        interpreter.on_warning(move |msg| sink.borrow_mut().push(msg.to_string()));
+
+        assert_eq!(interpreter.run("1 'a' 2 s"), Ok(ExecOutcome::Done));
+        assert_eq!(interpreter.stack(), &[StackVal::Number(3.0)]);
+        assert_eq!(
+            *warnings.borrow(),
+            ["while performing [Sum] at index 3, failed to SUM because non-number is in stack."]
+        );
+    }
+
+    #[test]
+    fn host_functions() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register("concat", 2, |stack| {
+            let b = stack.pop().unwrap();
+            let a = stack.pop().unwrap();
+            stack.push(StackVal::String(format!("{a}{b}")));
+            Ok(())
+        });
+        interpreter.register("fail", 0, |_| Err(BngError::Runtime("nope".into())));
+
+        assert_eq!(
+            parse_bng(":concat,:x_1".into()).unwrap(),
+            vec![Instr::FnCall("concat".into()), Instr::FnCall("x_1".into())]
+        );
+        assert!(parse_bng(": P".into()).is_err());
+
+        interpreter.run("'a' 'b' :concat").unwrap();
+        assert_eq!(interpreter.stack(), &[StackVal::String("ab".into())]);
+
+        assert!(interpreter.run(":concat").is_err());
+        assert_eq!(interpreter.stack().len(), 1);
+        assert!(interpreter.run(":missing").is_err());
+        assert!(interpreter.run(":fail").is_err());
+    }
+
+    /// The source of `examples/<name>`.
+    pub(crate) fn example_source(name: &str) -> String {
+        let path = format!("{}/examples/{name}", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn run_example(name: &str, input: &str) -> String {
+        let src = example_source(name);
+        let mut interpreter = Interpreter::with_io(input.as_bytes(), Vec::new());
+        interpreter.run(&src).unwrap();
+        String::from_utf8(interpreter.into_io().1).unwrap()
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(run_example("hello-world.bng", ""), "Hello world!\n");
+        assert_eq!(run_example("math-1.bng", ""), "21\n5 * 5 = 25\n");
+        assert_eq!(
+            run_example("input.bng", "bob\n"),
+            "enter name: your name is: bob\n"
+        );
+        assert_eq!(
+            run_example("input-math.bng", "4\n"),
+            "enter your number to add to 5: your number + 5 is: 9\n"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(
+            parse_bng("2 (1 (3P))".into()).unwrap(),
+            vec![
+                Instr::PushNum(2.0),
+                Instr::Loop(vec![
+                    Instr::PushNum(1.0),
+                    Instr::Loop(vec![Instr::PushNum(3.0), Instr::PrintLn]),
+                ]),
+            ]
+        );
+        assert!(parse_bng("2 (1P".into()).is_err());
+
+        let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+        interpreter.run("3 ('x'p) 0 ('y'p) 4 (1 1 = ? [1 0 $])").unwrap();
+        assert_eq!(interpreter.output(), b"xxx");
+    }
+
+    /// Runs `src` through the tree walker and the VM and checks that both
+    /// print the same and leave the same stack.
+    fn assert_vm_matches(src: &str, input: &str) {
+        let instructions = parse_bng(src.into()).unwrap();
+
+        let mut tree = Interpreter::with_io(input.as_bytes(), Vec::new());
+        let tree_res = tree.execute(&instructions);
+        let mut vm = Interpreter::with_io(input.as_bytes(), Vec::new());
+        let vm_res = vm.execute_chunk(&Chunk::compile(&instructions));
+
+        assert_eq!(tree_res.is_ok(), vm_res.is_ok(), "{src}: {tree_res:?} vs {vm_res:?}");
+        assert_eq!(tree.stack(), vm.stack(), "{src}");
+        assert_eq!(tree.into_io().1, vm.into_io().1, "{src}");
+    }
+
+    #[test]
+    fn vm_matches_tree_walker() {
+        for example in ["hello-world.bng", "math-1.bng", "input.bng", "input-math.bng"] {
+            assert_vm_matches(&example_source(example), "7\n");
+        }
+        assert_vm_matches("1 2 - 3 / 4 * 2 < ! ? ['yes'P] 'no'P", "");
+        assert_vm_matches("5 (1 2 + 3 3 = ? [d 'in'p] 2 (9)) S", "");
+        assert_vm_matches("3 (1 1 = ? [4 0 $]) 'unreachable'P", "");
+        assert_vm_matches("'a' 2 (1 +)", "");
+        assert_vm_matches("1 ?[2]", "");
+        assert_vm_matches("'x' (1)", "");
+    }
+
+    #[test]
+    fn test_asm() {
+        let instructions = vec![Instr::PushNum(1.0), Instr::Exit];
+        let out = std::env::temp_dir().join("bingbang-test_asm.out");
+        compile(&instructions, &out).unwrap();
+        assert!(std::fs::read_to_string(&out).unwrap().contains("fn main()"));
+    }
+
+    /// Compiles `instructions` for `target`, builds them with `build` and
+    /// checks that the binary prints what the interpreter prints and exits
+    /// with the same code. Says so on stderr and passes when a build tool
+    /// is missing, so run with `--nocapture` to see what was skipped.
+    fn assert_backend_matches(
+        target: Target,
+        name: &str,
+        instructions: &[Instr],
+        input: &str,
+        build: &[&[&str]],
+    ) {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let dir = std::env::temp_dir().join(format!(
+            "bingbang-{target:?}-{}-{name}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        compile_to(instructions, target, &dir.join(target.default_output())).unwrap();
+
+        for cmd in build {
+            match Command::new(cmd[0]).args(&cmd[1..]).current_dir(&dir).status() {
+                Ok(status) => assert!(status.success(), "{} failed on {name}", cmd[0]),
+                Err(e) => {
+                    eprintln!("skipped {target:?} backend test {name}: can't run `{}` ({e})", cmd[0]);
+                    return;
+                }
+            }
+        }
+
+        let mut child = Command::new(dir.join("out"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // the program may exit before reading all of its input
+        child.stdin.take().unwrap().write_all(input.as_bytes()).ok();
+        let compiled = child.wait_with_output().unwrap();
+
+        let mut interpreter = Interpreter::with_io(input.as_bytes(), Vec::new());
+        let code = match interpreter.execute(instructions) {
+            Ok(ExecOutcome::Exit(code)) => code,
+            Ok(ExecOutcome::Done) => 0,
+            Err(_) => 1,
+        };
+
+        assert_eq!(compiled.status.code(), Some(code), "{name}");
+        assert_eq!(compiled.stdout, interpreter.into_io().1, "{name}");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    const RUSTC: &[&[&str]] = &[&["rustc", "-O", "out.rs", "-o", "out"]];
+
+    /// Skipped when `rustc` isn't installed.
+    fn assert_rust_backend_matches(name: &str, src: &str, input: &str) {
+        let instructions = parse_bng(src.into()).unwrap();
+        assert_backend_matches(Target::Rust, name, &instructions, input, RUSTC);
+    }
+
+    /// Skipped when `cc` isn't installed.
+    fn assert_c_backend_matches(name: &str, src: &str, input: &str) {
+        let build: &[&[&str]] = &[&[
+            "cc", "-std=c99", "-O2", "-Wall", "-Wno-unused-function", "-Werror", "out.c", "-o", "out",
+            "-lm",
+        ]];
+        assert_backend_matches(Target::C, name, &parse_bng(src.into()).unwrap(), input, build);
+    }
+
+    /// Skipped when binutils aren't installed or this isn't x86-64 Linux.
+    fn assert_asm_backend_matches(name: &str, src: &str) {
+        if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+            eprintln!("skipped Asm backend test {name}: this isn't x86-64 Linux");
+            return;
+        }
+        let build: &[&[&str]] = &[&["as", "out.s", "-o", "out.o"], &["ld", "out.o", "-o", "out"]];
+        assert_backend_matches(Target::Asm, name, &parse_bng(src.into()).unwrap(), "", build);
+    }
+
+    #[test]
+    fn rust_backend_matches_interpreter() {
+        for example in ["hello-world.bng", "math-1.bng", "input.bng", "input-math.bng"] {
+            let src = example_source(example);
+            assert_rust_backend_matches(example, &src, "7\n");
+        }
+        assert_rust_backend_matches(
+            "misc",
+            "'a\\\"b'P 3 ('x'p 2 4 > ! ? ['y'P]) '12'n 0 1 = R S 10 0. 'ab'r A 1 1 = ? [5 0 $]",
+            "",
+        );
+        assert_rust_backend_matches("overflow", "999999999999999999999999999999999999999999 P 0 1 - P", "");
+
+        let folded = optimize::optimize(&parse_bng("0 0 / P 1 0 / P 0 1 0 / - P".into()).unwrap());
+        assert!(folded.iter().all(|inst| matches!(inst, Instr::PushNum(_) | Instr::PrintLn)));
+        assert_backend_matches(Target::Rust, "folded", &folded, "", RUSTC);
+
+        assert_rust_backend_matches("time", "'%Y %C %y %G %g%n%t%%' T P 1 T", "");
+    }
+
+    #[test]
+    fn asm_backend_matches_interpreter() {
+        for example in ["hello-world.bng", "math-1.bng"] {
+            let src = example_source(example);
+            assert_asm_backend_matches(example, &src);
+        }
+        assert_asm_backend_matches(
+            "misc",
+            "'a\\\"b'P 3 ('x'p 2 4 > ! ? ['y'P]) 1 3 / P 2 8 / 3 - P 7 '7' = P 'x' 1 = P 1 1 = ? [5 0 $]",
+        );
+        assert_asm_backend_matches("error", "'x'P 'a' 1 +");
+        // non-numbers are left out of a sum, not an error
+        assert_asm_backend_matches("sum", "'x' 1 2 s P 1 1 = 3 s P 'y' s P");
+
+        let unsupported = parse_bng("'a'P i".into()).unwrap();
+        let out = std::env::temp_dir().join("bingbang-unsupported.s");
+        assert!(compile_to(&unsupported, Target::Asm, &out).is_err());
+    }
+
+    #[test]
+    fn c_backend_matches_interpreter() {
+        for example in ["hello-world.bng", "math-1.bng", "input.bng", "input-math.bng"] {
+            let src = example_source(example);
+            assert_c_backend_matches(example, &src, "7\n");
+        }
+        assert_c_backend_matches(
+            "misc",
+            "'a\\\"b?'P 3 ('x'p 2 4 > ! ? ['y'P]) '12'n 0 1 = R S 10 0. 'h\u{e9}llo'r A 1 3 / P 1 1 = ? [5 0 $]",
+            "",
+        );
+        assert_c_backend_matches("flip", "1 2 3 R S d s P 7 '7' = P 'true' 1 1 = = P 4 5 a", "");
+        assert_c_backend_matches("read", "i n ' 2.5 'n + P i", "1.5\n");
+        assert_c_backend_matches("time", "'%Y %C %y %G %g%n%t%%' T P", "");
+    }
+
+    #[test]
+    fn rust_and_c_check_constant_time_formats() {
+        for target in [Target::Rust, Target::C] {
+            let emit = |src: &str| target.emit(&parse_bng(src.into()).unwrap());
+            assert_eq!(
+                emit("1 P '%d %-m' T").unwrap_err(),
+                BngError::Runtime(
+                    "while compiling [TimeFmt] at index 3, `%-m` is not a C99 strftime specifier.\n    \
+                     %d %-m\n       ^^^"
+                        .into()
+                )
+            );
+            assert!(emit("'%Z' T").is_err());
+            assert!(emit("'%Q' T").is_err());
+            assert!(emit("t '%F %T' T 'x' i T").is_ok());
+        }
+    }
+
+    #[test]
+    fn rust_and_c_reject_date_instructions() {
+        let now = "'2024-02-29T12:00:00Z'";
+        for (src, name) in [
+            ("w".to_string(), "Now"),
+            ("e".into(), "Epoch"),
+            ("E".into(), "EpochMillis"),
+            ("'1' '%s' W".into(), "ParseTime"),
+            (format!("{now} '%Y' F"), "FormatTime"),
+            (format!("{now} 1 D"), "AddTime"),
+            (format!("{now} {now} G"), "TimeDiff"),
+            (format!("{now} 'utc' z"), "InZone"),
+        ] {
+            let instructions = parse_bng(src).unwrap();
+            let idx = instructions.len() - 1;
+            for (target, id) in [(Target::Rust, "rust"), (Target::C, "c")] {
+                assert_eq!(
+                    target.emit(&instructions),
+                    Err(BngError::Runtime(format!(
+                        "the {id} target doesn't support [{name}] (at index {idx})."
+                    )))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn wat_backend() {
+        let src = "1 2 + P 3 (2 (1 1 = ! ? [5P] 4 p)) i n 2 < P 7 0 $";
+        let wat = Target::Wat.emit(&parse_bng(src.into()).unwrap()).unwrap();
+
+        let mut depth = 0;
+        for line in wat.lines() {
+            for c in line.split(";;").next().unwrap().chars() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                assert!(depth >= 0, "unbalanced: {line}");
+            }
+        }
+        assert_eq!(depth, 0);
+
+        for call in wat.split("(call $").skip(1) {
+            let name = call.split([' ', ')']).next().unwrap();
+            let defined = wat.match_indices(&format!("(func ${name}")).any(|(at, def)| {
+                !matches!(wat.as_bytes()[at + def.len()], b'_' | b'a'..=b'z')
+            });
+            assert!(defined, "${name} is called but not defined");
+        }
+        assert!(wat.contains("(func (export \"main\")"));
+        assert!(wat.contains("(local $l2 i64)"));
+        assert!(!wat.contains("$l3"));
+
+        let out = std::env::temp_dir().join("bingbang-unsupported.wat");
+        assert!(compile_to(&parse_bng("'a'P".into()).unwrap(), Target::Wat, &out).is_err());
+
+        // a real check when wabt is installed
+        let path = std::env::temp_dir().join(format!("bingbang-{}.wat", std::process::id()));
+        std::fs::write(&path, &wat).unwrap();
+        match std::process::Command::new("wat2wasm")
+            .arg(&path)
+            .arg("-o")
+            .arg(path.with_extension("wasm"))
+            .status()
+        {
+            Ok(status) => assert!(status.success(), "wat2wasm rejected the module"),
+            Err(e) => eprintln!("skipped validating the Wat backend: can't run `wat2wasm` ({e})"),
+        }
+    }
+}
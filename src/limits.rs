@@ -0,0 +1,283 @@
+//! Caps on what a program may use, for running code you don't trust. A run
+//! that goes over one fails with [`BngError::Limit`].
+//!
+//! ```
+//! use bingbang::{BngError, Interpreter, Limits};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.set_limits(Limits {
+//!     stack: Some(1000),
+//!     ..Limits::default()
+//! });
+//! assert!(matches!(interpreter.run("2000000000 0 ."), Err(BngError::Limit(_))));
+//! ```
+
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// `None` leaves a resource uncapped, which is the default for all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// Instructions performed in one run, or ops on the bytecode vm. A
+    /// range also costs one for each value it pushes.
+    pub fuel: Option<u64>,
+    /// Values on the stack.
+    pub stack: Option<usize>,
+    /// Ifs and loops nested inside each other.
+    pub depth: Option<usize>,
+    /// Bytes in a string.
+    pub string: Option<usize>,
+    /// Wall-clock time of one run. A program waiting for input isn't
+    /// stopped until the input arrives.
+    pub time: Option<Duration>,
+}
+
+/// What the current run has used.
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    /// Whether any limit is set.
+    active: bool,
+    fuel: u64,
+    depth: usize,
+    deadline: Option<Instant>,
+    /// The kind of error the run failed with, when it isn't a plain
+    /// runtime error.
+    pub(crate) failure: Option<fn(String) -> BngError>,
+}
+
+/// How many instructions run between looking at the clock.
+const CLOCK_INTERVAL: u64 = 1024;
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    /// Sets the limits for the following runs.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Starts counting for a new run.
+    pub(crate) fn start_run(&mut self) {
+        self.usage = Usage {
+            active: self.limits != Limits::default(),
+            deadline: self.limits.time.map(|time| Instant::now() + time),
+            ..Usage::default()
+        };
+    }
+
+    /// Whether any limit applies to this run. Without one, [`Self::tick`]
+    /// and [`Self::check_stack`] can't fail, and hot loops skip them.
+    pub(crate) fn limited(&self) -> bool {
+        self.usage.active
+    }
+
+    /// Turns an error of the run into a [`BngError`].
+    pub(crate) fn run_error(&mut self, e: String) -> BngError {
+        self.usage.failure.unwrap_or(BngError::Runtime)(e)
+    }
+
+    fn exceeded(&mut self, e: String) -> String {
+        self.usage.failure = Some(BngError::Limit);
+        e
+    }
+
+    /// Charges one instruction, before it runs.
+    pub(crate) fn tick(&mut self) -> Result<(), String> {
+        self.usage.fuel += 1;
+        if let Some(fuel) = self.limits.fuel {
+            if self.usage.fuel > fuel {
+                return Err(self.exceeded(format!("ran out of fuel after {fuel} instructions.")));
+            }
+        }
+        if let Some(deadline) = self.usage.deadline {
+            if self.usage.fuel.is_multiple_of(CLOCK_INTERVAL) && Instant::now() > deadline {
+                let time = self.limits.time.unwrap();
+                return Err(self.exceeded(format!("timed out after {time:?}.")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks what the instruction at `idx` left on the stack. Only the top
+    /// value can be new, so only its length is checked.
+    pub(crate) fn check_stack(&mut self, idx: usize) -> Result<(), String> {
+        self.make_room(0, idx)?;
+        if let (Some(max), Some(StackVal::String(s))) = (self.limits.string, self.stack.last()) {
+            if s.len() > max {
+                return Err(
+                    self.exceeded(format!("at index {idx}, a string grew past {max} bytes."))
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if `more` values wouldn't fit on the stack, so a big push can
+    /// be refused before it happens.
+    pub(crate) fn make_room(&mut self, more: usize, idx: usize) -> Result<(), String> {
+        match self.limits.stack {
+            Some(max) if self.stack.len().saturating_add(more) > max => {
+                Err(self.exceeded(format!("at index {idx}, the stack grew past {max} values.")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Enters the body of an if or loop; [`Self::leave`] must follow.
+    pub(crate) fn enter(&mut self, idx: usize) -> Result<(), String> {
+        self.usage.depth += 1;
+        match self.limits.depth {
+            Some(max) if self.usage.depth > max => Err(self.exceeded(format!(
+                "at index {idx}, bodies are nested more than {max} deep."
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.usage.depth -= 1;
+    }
+
+    /// The most bytes `i` should read for one line, with room for the line
+    /// ending.
+    pub(crate) fn max_line(&self) -> u64 {
+        self.limits.string.map_or(u64::MAX, |max| max as u64 + 2)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(src: &str, input: &str, limits: Limits) -> Result<ExecOutcome, BngError> {
+        let mut interpreter = Interpreter::with_io(input.as_bytes(), Vec::new());
+        interpreter.set_limits(limits);
+        interpreter.run(src)
+    }
+
+    fn chunk(src: &str, limits: Limits) -> Result<ExecOutcome, BngError> {
+        let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+        interpreter.set_limits(limits);
+        interpreter.execute_chunk(&bytecode::Chunk::compile(&parse_bng(src.into()).unwrap()))
+    }
+
+    #[test]
+    fn stops_runaway_programs() {
+        let fuel = Limits {
+            fuel: Some(100),
+            ..Limits::default()
+        };
+        assert_eq!(run("10 (1 d)", "", fuel.clone()), Ok(ExecOutcome::Done));
+        let e = run("100 (1 d)", "", fuel.clone());
+        assert_eq!(
+            e,
+            Err(BngError::Limit(
+                "ran out of fuel after 100 instructions.".into()
+            ))
+        );
+        assert!(matches!(chunk("100 (1 d)", fuel.clone()), Err(BngError::Limit(_))));
+
+        let stack = Limits {
+            stack: Some(10),
+            ..Limits::default()
+        };
+        assert_eq!(run("10 0 .", "", stack.clone()), Ok(ExecOutcome::Done));
+        let e = run("2000000000 0 .", "", stack.clone());
+        assert_eq!(
+            e,
+            Err(BngError::Limit(
+                "at index 2, the stack grew past 10 values.".into()
+            ))
+        );
+        assert!(matches!(
+            run("20 (1)", "", stack.clone()),
+            Err(BngError::Limit(_))
+        ));
+        assert!(matches!(chunk("20 (1)", stack), Err(BngError::Limit(_))));
+
+        let depth = Limits {
+            depth: Some(2),
+            ..Limits::default()
+        };
+        assert_eq!(run("1 (1 (2 d))", "", depth.clone()), Ok(ExecOutcome::Done));
+        assert!(matches!(
+            run("1 (1 (1 (2 d)))", "", depth),
+            Err(BngError::Limit(_))
+        ));
+
+        let string = Limits {
+            string: Some(5),
+            ..Limits::default()
+        };
+        assert_eq!(run("i r", "hello\n", string.clone()), Ok(ExecOutcome::Done));
+        assert!(matches!(
+            run("i", "hello world\n", string.clone()),
+            Err(BngError::Limit(_))
+        ));
+        assert!(matches!(
+            run("'abcdef'", "", string),
+            Err(BngError::Limit(_))
+        ));
+
+        let time = Limits {
+            time: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        let e = run("1000000000 (1 d)", "", time.clone());
+        assert_eq!(e, Err(BngError::Limit("timed out after 10ms.".into())));
+        assert!(matches!(
+            chunk("1000000000 (1 d)", time.clone()),
+            Err(BngError::Limit(_))
+        ));
+
+        // a range is charged for each value, not once
+        let e = run("1000 0 . c", "", fuel.clone());
+        assert_eq!(
+            e,
+            Err(BngError::Limit(
+                "ran out of fuel after 100 instructions.".into()
+            ))
+        );
+        assert!(matches!(chunk("1000 0 . c", fuel), Err(BngError::Limit(_))));
+        let e = run("30000000 0 . c", "", time);
+        assert_eq!(e, Err(BngError::Limit("timed out after 10ms.".into())));
+
+        // errors that aren't about limits stay runtime errors
+        let all = Limits {
+            fuel: Some(5),
+            stack: Some(5),
+            ..Limits::default()
+        };
+        assert!(matches!(run("1 'a' +", "", all), Err(BngError::Runtime(_))));
+    }
+
+    #[test]
+    fn engines_count_depth_alike() {
+        let depth = |max| Limits {
+            depth: Some(max),
+            ..Limits::default()
+        };
+        for (src, max, ok) in [
+            ("1 1 = ? ['a' P]", 0, false),
+            ("1 2 = ? [1 (2 d)]", 0, true),
+            ("1 1 = ? [2 d] 1 (2 d)", 1, true),
+            ("1 1 = ? [1 1 = ? ['a' P]]", 1, false),
+            ("1 (1 1 = ? [2 d])", 1, false),
+        ] {
+            let walked = run(src, "", depth(max));
+            let ran = chunk(src, depth(max));
+            assert_eq!(walked.is_ok(), ok, "`{src}` on the tree walker: {walked:?}");
+            assert_eq!(ran.is_ok(), ok, "`{src}` on the vm: {ran:?}");
+            assert!(ok || matches!(ran, Err(BngError::Limit(_))), "{ran:?}");
+        }
+        // an error at the top level is at the same index on both
+        assert_eq!(
+            chunk("1 1 = ? ['a' P]", depth(0)),
+            run("1 1 = ? ['a' P]", "", depth(0))
+        );
+    }
+}
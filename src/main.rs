@@ -1,152 +1,306 @@
-use std::{env, fs, path::Path};
-
-mod errhandling;
-mod instruction;
-mod interpret;
-mod parse;
-mod stackval;
-mod compile;
-
-use instruction::*;
-use interpret::interpret_instructions;
-use parse::parse_bng;
-use stackval::StackVal;
-use compile::compile;
-
-fn perform_math_op(operands: (f32, f32), operation: &Instr) -> StackVal {
-    match operation {
-        Instr::Plus => StackVal::Number(operands.0 + operands.1),
-        Instr::Div => StackVal::Number(operands.0 / operands.1),
-        Instr::Sub => StackVal::Number(operands.0 - operands.1),
-        Instr::Mul => StackVal::Number(operands.0 * operands.1),
-        _ => unimplemented!(),
-    }
-}
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::{env, path::Path, process::exit};
 
-fn main() {
-    let args = env::args().collect::<Vec<String>>();
-
-    if args.len() != 2 {
-        err!("expected 1 arg, got {}", args.len() - 1);
-    }
+mod cli;
 
-    let fname = &args[1];
+use bingbang::bngc;
+use bingbang::bytecode::Chunk;
+use bingbang::check::{check, infer, Diagnostic, Severity};
+use bingbang::compile::compile_to;
+use bingbang::debug::Debugger;
+use bingbang::errhandling::{self, BngError, EXIT_USAGE};
+use bingbang::instruction::INTERACTIVE_COMMENT;
+use bingbang::optimize::optimize;
+use bingbang::parse::SourceMap;
+use bingbang::profile::Profiler;
+use bingbang::trace::{Format, Tracer};
+use bingbang::{err, parse_spanned, pflush, ExecOutcome, Instr, Interpreter, ParseOptions};
+use cli::{parse_args, Command, Options, Source, Trace};
 
-    if *fname == "-i".to_string() {
-        // interpreter
-        let mut buf = String::new();
-        let stdin = std::io::stdin();
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
 
-        println!("\x1b[92mbingbang interpreter\n\x1b[93m   -> press q to quit\n   -> ; to comment\n\x1b[0m");
+    let opts = match parse_args(&args) {
+        Ok(v) => v,
+        Err(e) => {
+            err!("{}\n(see `bingbang --help`)", e);
+            exit(EXIT_USAGE);
+        }
+    };
 
-        let mut stack: Vec<StackVal> = Vec::new();
+    errhandling::set_color(opts.color);
 
-        loop {
-            print!("\x1b[93m>\x1b[0m");
+    match run(&opts) {
+        Ok(0) => (),
+        Ok(code) => {
+            pflush!();
+            exit(code);
+        }
+        Err(e) => {
             pflush!();
+            err!(e);
+            exit(e.exit_code());
+        }
+    }
+}
 
-            buf.clear();
-            stdin
-                .read_line(&mut buf)
-                .expect("failed to read from stdin");
+/// Runs the command and returns the exit code the program asked for.
+fn run(opts: &Options) -> Result<i32, BngError> {
+    let source = match opts.command {
+        Command::Help => {
+            print!("{}", cli::HELP);
+            return Ok(0);
+        }
+        Command::Version => {
+            println!("bingbang {}", env!("CARGO_PKG_VERSION"));
+            return Ok(0);
+        }
+        Command::Repl => {
+            repl(opts);
+            return Ok(0);
+        }
+        _ => opts.source.as_ref().unwrap(),
+    };
 
-            if buf.starts_with(INTERACTIVE_COMMENT) {
-                continue;
+    let (name, content) = read_source(source).map_err(BngError::Io)?;
+    let (mut instructions, map, src) = load_program(&name, content)?;
+    // the checker and debugger work on the source as written
+    if opts.optimize && !matches!(opts.command, Command::Check | Command::Debug) {
+        let optimized = optimize(&instructions);
+        if opts.command == Command::Dump {
+            println!("before:\n{instructions:#?}\n\nafter:");
+        }
+        instructions = optimized;
+    }
+
+    match opts.command {
+        Command::Check => {
+            let diagnostics = check(&instructions, &map);
+            for diagnostic in &diagnostics {
+                report(&name, &src, diagnostic);
+            }
+            let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error);
+            match errors.count() {
+                0 if !opts.quiet => println!("{name}: ok"),
+                0 => {}
+                1 => return Err(BngError::Runtime(format!("{name}: found 1 problem."))),
+                n => return Err(BngError::Runtime(format!("{name}: found {n} problems."))),
+            }
+        }
+        Command::Debug => {
+            if !opts.quiet {
+                let banner = format!("debugging {name}, type `help` for commands");
+                println!("{}\n", errhandling::paint(93, &banner));
+            }
+            let mut debugger = Debugger::new(&src, map);
+            let mut interpreter = interpreter(opts);
+            let outcome = interpreter
+                .execute_hooked(&instructions, &mut debugger)
+                .map_err(|e| e.context(&name))?;
+            if let ExecOutcome::Exit(code) = outcome {
+                return Ok(code);
+            }
+        }
+        Command::Dump if opts.types => {
+            // spans don't survive optimizing, but the types don't need them
+            let (_, types) = infer(&instructions, &SourceMap::default());
+            print!("{}", types.annotate(&instructions));
+        }
+        Command::Dump => println!("{instructions:#?}"),
+        Command::Compile if opts.bytecode => {
+            let out = opts.output.as_deref().unwrap_or("out.bngc");
+            fs::write(out, bngc::encode(&instructions))
+                .map_err(|e| BngError::Io(format!("failed to write `{out}`: {e}")))?;
+        }
+        Command::Compile => {
+            let out = opts.output.as_deref().unwrap_or(opts.target.default_output());
+            compile_to(&instructions, opts.target, Path::new(out))?;
+        }
+        _ => {
+            if !opts.quiet && matches!(source, Source::File(_)) {
+                println!("{}\n", errhandling::paint(93, &name));
             }
 
-            let mut instructions = match parse_bng(buf.trim_end().into()) {
-                Ok(v) => v,
-                Err(e) => {
-                    err!(e);
-                    continue;
-                }
-            };
-            if instructions.len() >= 1 {
-                instructions.pop();
+            let mut interpreter = interpreter(opts);
+            let outcome = if opts.vm {
+                interpreter.execute_chunk(&Chunk::compile(&instructions))
+            } else if let Some(trace) = &opts.trace {
+                // optimizing moves instructions away from their spans
+                let map = if opts.optimize { SourceMap::default() } else { map };
+                run_traced(&mut interpreter, &instructions, trace, &src, map)
+            } else if opts.profile {
+                let map = if opts.optimize { SourceMap::default() } else { map };
+                run_profiled(&mut interpreter, &instructions, opts.folded.as_deref(), &src, map)
+            } else {
+                interpreter.execute(&instructions)
             }
+            .map_err(|e| e.context(&name))?;
 
-            match interpret_instructions(&instructions, &mut stack) {
-                Ok(_) => (),
-                Err(e) => err!(e),
+            if let ExecOutcome::Exit(code) = outcome {
+                return Ok(code);
             }
         }
     }
 
-    if !Path::new(fname).is_file() {
-        err!("file `{fname}` does not exist.");
-    }
+    Ok(0)
+}
 
-    let content = fs::read_to_string(fname).unwrap();
+/// An interpreter with the limits, capabilities and clock from the command
+/// line.
+fn interpreter(opts: &Options) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(opts.limits.clone());
+    interpreter.set_clock(opts.clock);
+    for &capability in &opts.denied {
+        interpreter.deny(capability);
+    }
+    interpreter
+}
 
-    let instructions = match parse_bng(content) {
-        Ok(v) => v,
-        Err(e) => {
-            err!(e);
-            std::process::exit(1);
+/// Runs `instructions`, logging each one to where `trace` says.
+fn run_traced(
+    interpreter: &mut Interpreter,
+    instructions: &[Instr],
+    trace: &Trace,
+    src: &str,
+    map: SourceMap,
+) -> Result<ExecOutcome, BngError> {
+    let (out, format): (Box<dyn Write>, _) = match trace {
+        Trace::Stderr => (Box::new(io::stderr().lock()), Format::Text),
+        Trace::File(path) => {
+            let file = File::create(path)
+                .map_err(|e| BngError::Io(format!("failed to create `{path}`: {e}")))?;
+            (Box::new(BufWriter::new(file)), Format::Json)
         }
     };
+    let mut tracer = Tracer::new(out, format, src, map);
+    let outcome = interpreter.execute_hooked(instructions, &mut tracer);
+    tracer
+        .into_inner()
+        .flush()
+        .map_err(|e| BngError::Io(format!("failed to write the trace: {e}")))?;
+    outcome
+}
 
-    println!("instructions = {instructions:#?}");
-    println!("\x1b[93m{fname}\x1b[0m\n");
-
-    let mut stack: Vec<StackVal> = Vec::new();
-
-    match interpret_instructions(&instructions, &mut stack) {
-        Ok(_) => (),
-        Err(e) => err!(e),
+/// Runs `instructions` and prints where the time went, even if they fail.
+fn run_profiled(
+    interpreter: &mut Interpreter,
+    instructions: &[Instr],
+    folded: Option<&str>,
+    src: &str,
+    map: SourceMap,
+) -> Result<ExecOutcome, BngError> {
+    let mut profiler = Profiler::new(src, map);
+    let outcome = interpreter.execute_hooked(instructions, &mut profiler);
+    pflush!();
+    eprint!("\n{}", profiler.report());
+    if let Some(path) = folded {
+        fs::write(path, profiler.folded())
+            .map_err(|e| BngError::Io(format!("failed to write `{path}`: {e}")))?;
     }
+    outcome
 }
 
-#[cfg(test)]
-mod test {
-    use crate::{parse_bng, Instr, compile::compile};
-    #[test]
-    fn hello_world() {
-        let parsed = parse_bng("'Hello World'P".into()).unwrap();
-        assert!(
-            parsed
-                == vec![
-                    Instr::PushStr("Hello World".into()),
-                    Instr::PrintLn,
-                    Instr::Exit
-                ]
-        );
+/// Returns the display name and contents of the program.
+fn read_source(source: &Source) -> Result<(String, Vec<u8>), String> {
+    match source {
+        Source::File(fname) => {
+            if !Path::new(fname).is_file() {
+                return Err(format!("file `{fname}` does not exist."));
+            }
+            let content = fs::read(fname)
+                .map_err(|e| format!("failed to read `{fname}`: {e}"))?;
+            Ok((fname.clone(), content))
+        }
+        Source::Stdin => {
+            let mut content = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut content)
+                .map_err(|e| format!("failed to read program from stdin: {e}"))?;
+            Ok(("<stdin>".into(), content))
+        }
+        Source::Inline(code) => Ok(("<eval>".into(), code.clone().into_bytes())),
     }
-    #[test]
-    fn basic_math() {
-        let parsed = parse_bng("1 2 3 + P".into()).unwrap();
-        assert!(
-            parsed
-                == vec![
-                    Instr::PushNum(1.0),
-                    Instr::PushNum(2.0),
-                    Instr::PushNum(3.0),
-                    Instr::Plus,
-                    Instr::PrintLn,
-                    Instr::Exit
-                ]
-        );
+}
+
+/// Decodes a compiled `.bngc` program, or parses source. Returns the
+/// instructions, where they are in the source and the source itself; the
+/// last two are empty for `.bngc` files.
+fn load_program(name: &str, content: Vec<u8>) -> Result<(Vec<Instr>, SourceMap, String), BngError> {
+    if bngc::is_bngc(&content) {
+        let instructions =
+            bngc::decode(&content).map_err(|e| BngError::Parse(format!("{name}: {e}")))?;
+        return Ok((instructions, SourceMap::default(), String::new()));
     }
+    let src = String::from_utf8(content)
+        .map_err(|_| BngError::Parse(format!("{name}: source is not valid utf-8.")))?;
+    let (instructions, map) = parse_spanned(src.clone(), &ParseOptions::default())
+        .map_err(|e| BngError::Parse(format!("{name}: {e}")))?;
+    Ok((instructions, map, src))
+}
+
+/// Prints `diagnostic` with the line it points at and the span underlined.
+fn report(name: &str, src: &str, diagnostic: &Diagnostic) {
+    let (color, label) = match diagnostic.severity {
+        Severity::Error => (91, "[ERROR]"),
+        Severity::Warning => (93, "[WARNING]"),
+    };
+    let label = errhandling::paint(color, label);
+
+    let Some(span) = diagnostic.span else {
+        eprintln!("{label}: {name}: {}", diagnostic.message);
+        return;
+    };
+    let (line, col) = span.line_col(src);
+    eprintln!("{label}: {name}:{line}:{col}: {}", diagnostic.message);
+
+    let text = src.lines().nth(line - 1).unwrap_or("");
+    let rest = text.chars().count().saturating_sub(col - 1).max(1);
+    let width = (span.end - span.start).clamp(1, rest);
+    eprintln!("    {text}");
+    eprintln!("    {}{}", " ".repeat(col - 1), errhandling::paint(color, &"^".repeat(width)));
+}
+
+fn repl(opts: &Options) {
+    let mut buf = String::new();
 
-    #[test]
-    fn parse_numbers() {
-        let parsed = parse_bng("1 '1'n + P".into()).unwrap();
-        assert!(
-            parsed
-                == vec![
-                    Instr::PushNum(1.0),
-                    Instr::PushStr("1".into()),
-                    Instr::ParseNum,
-                    Instr::Plus,
-                    Instr::PrintLn,
-                    Instr::Exit
-                ]
+    if !opts.quiet {
+        println!(
+            "{}\n{}\n",
+            errhandling::paint(92, "bingbang interpreter"),
+            errhandling::paint(93, "   -> press q to quit\n   -> ; to comment")
         );
     }
 
-    #[test]
-    fn test_asm() {
-        let mut instructions = vec![Instr::PushNum(1.0), Instr::Exit];
-        compile(&mut instructions);
+    let mut interpreter = interpreter(opts);
+
+    loop {
+        print!("{}", errhandling::paint(93, ">"));
+        pflush!();
+
+        buf.clear();
+        // read through the interpreter so lines typed for `i` aren't lost
+        // in a second stdin buffer
+        let read = interpreter
+            .input()
+            .read_line(&mut buf)
+            .expect("failed to read from stdin");
+
+        if read == 0 {
+            // EOF
+            println!();
+            return;
+        }
+
+        if buf.starts_with(INTERACTIVE_COMMENT) {
+            continue;
+        }
+
+        match interpreter.run(buf.trim_end()) {
+            Ok(ExecOutcome::Exit(_)) => return,
+            Ok(ExecOutcome::Done) => (),
+            Err(e) => err!(e),
+        }
     }
 }
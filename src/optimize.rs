@@ -0,0 +1,132 @@
+//! A peephole pass that evaluates what it can before the program runs:
+//! arithmetic, comparisons and equality on constants, `!` of known bools,
+//! ifs whose condition is known, and values that are pushed only to be
+//! popped. The result behaves like the input, though the indices in error
+//! messages can differ.
+//!
+//! ```
+//! use bingbang::{optimize::optimize, parse_bng, Instr};
+//!
+//! let instructions = parse_bng("5 5 * P".into()).unwrap();
+//! assert_eq!(optimize(&instructions), [Instr::PushNum(25.0), Instr::PrintLn]);
+//! ```
+
+use crate::*;
+
+pub fn optimize(instructions: &[Instr]) -> Vec<Instr> {
+    let mut out = Vec::with_capacity(instructions.len());
+    for inst in instructions {
+        push(&mut out, inst.clone());
+    }
+    out
+}
+
+/// Appends `inst` to `out`, folding it into the instructions before it
+/// while that's possible.
+fn push(out: &mut Vec<Instr>, inst: Instr) {
+    let inst = match inst {
+        Instr::IfStmt(body) => Instr::IfStmt(optimize(&body)),
+        Instr::Loop(body) => Instr::Loop(optimize(&body)),
+        inst => inst,
+    };
+
+    let len = out.len();
+    let folded = match (out.get(len.wrapping_sub(2)..), &inst) {
+        (
+            Some([Instr::PushNum(b), Instr::PushNum(a)]),
+            Instr::Plus | Instr::Sub | Instr::Mul | Instr::Div,
+        ) => match perform_math_op((*a, *b), &inst) {
+            Some(StackVal::Number(n)) => Some((2, vec![Instr::PushNum(n)])),
+            _ => None,
+        },
+        (Some([Instr::PushNum(b), Instr::PushNum(a)]), Instr::GreaterThan) => {
+            Some((2, vec![Instr::PushBool(a > b)]))
+        }
+        (Some([Instr::PushNum(b), Instr::PushNum(a)]), Instr::LessThan) => {
+            Some((2, vec![Instr::PushBool(a < b)]))
+        }
+        (Some([b, a]), Instr::Eq) => match (constant(a), constant(b)) {
+            (Some(a), Some(b)) => Some((2, vec![Instr::PushBool(a == b)])),
+            _ => None,
+        },
+        _ => match (out.last(), &inst) {
+            (Some(Instr::PushBool(b)), Instr::Not) => Some((1, vec![Instr::PushBool(!b)])),
+            (Some(Instr::PushBool(true)), Instr::IfStmt(body)) => Some((1, body.clone())),
+            (Some(Instr::PushBool(false)), Instr::IfStmt(_)) => Some((1, vec![])),
+            (Some(last), Instr::Pop) if constant(last).is_some() => Some((1, vec![])),
+            _ => None,
+        },
+    };
+
+    match folded {
+        Some((consumed, replacement)) => {
+            out.truncate(len - consumed);
+            // the replacement may enable another fold with what came before it
+            for inst in replacement {
+                push(out, inst);
+            }
+        }
+        None => out.push(inst),
+    }
+}
+
+/// The value `inst` pushes, if it only pushes a constant.
+fn constant(inst: &Instr) -> Option<StackVal> {
+    match inst {
+        Instr::PushNum(n) => Some(StackVal::Number(*n)),
+        Instr::PushStr(s) => Some(StackVal::String(s.clone())),
+        Instr::PushBool(b) => Some(StackVal::Bool(*b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn opt(src: &str) -> Vec<Instr> {
+        optimize(&parse_bng(src.into()).unwrap())
+    }
+
+    #[test]
+    fn folds_constants() {
+        use Instr::*;
+
+        assert_eq!(
+            opt("1 2 + 3 4 + 5 6 + s"),
+            [PushNum(3.0), PushNum(7.0), PushNum(11.0), Sum]
+        );
+        assert_eq!(opt("2 8 / 1 -"), [PushNum(-3.0)]);
+        assert_eq!(opt("1 2 < !"), [PushBool(true)]);
+        assert_eq!(opt("'1' 1 ="), [PushBool(true)]);
+        assert_eq!(
+            opt("2 1 > ? ['no'P] 1 2 > ? [2 2 * ? ['x'P]]"),
+            [PushNum(4.0), IfStmt(vec![PushStr("x".into()), PrintLn])]
+        );
+        assert_eq!(
+            opt("'a' d 3 (1 1 + d i d)"),
+            [PushNum(3.0), Loop(vec![Read, Pop])]
+        );
+        assert_eq!(
+            opt("i 1 + 2 * d i 1 ="),
+            [Read, PushNum(1.0), Plus, PushNum(2.0), Mul, Pop, Read, PushNum(1.0), Eq]
+        );
+    }
+
+    #[test]
+    fn keeps_behavior() {
+        let src = "1 2 + P 3 4 < ! ? ['a'P] 5 (1 1 = ? [2 d 'b'p]) 1 2 'x' + 7 0 $";
+        let instructions = parse_bng(src.into()).unwrap();
+        let optimized = optimize(&instructions);
+        assert!(optimized.len() < instructions.len());
+
+        let mut before = Interpreter::with_io(&b""[..], Vec::new());
+        let mut after = Interpreter::with_io(&b""[..], Vec::new());
+        assert_eq!(
+            before.execute(&instructions).is_ok(),
+            after.execute(&optimized).is_ok()
+        );
+        assert_eq!(before.stack(), after.stack());
+        assert_eq!(before.into_io().1, after.into_io().1);
+    }
+}
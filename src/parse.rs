@@ -1,77 +1,139 @@
 use crate::*;
 
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// Append an [`Instr::Exit`] after the last instruction.
+    pub implicit_exit: bool,
+}
+
+/// Parses a program into its instructions. The end of the program is left
+/// to the runner; use [`parse_bng_with`] to get a trailing [`Instr::Exit`].
 pub fn parse_bng(line: String) -> Result<Vec<Instr>, String> {
+    parse_bng_with(line, &ParseOptions::default())
+}
+
+pub fn parse_bng_with(line: String, opts: &ParseOptions) -> Result<Vec<Instr>, String> {
+    parse_spanned(line, opts).map(|(instructions, _)| instructions)
+}
+
+/// Where an instruction is in the source, in chars from its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The 1-based line and column of the start of the span in `src`.
+    pub fn line_col(&self, src: &str) -> (usize, usize) {
+        let mut pos = (1, 1);
+        for c in src.chars().take(self.start) {
+            if c == '\n' {
+                pos = (pos.0 + 1, 1);
+            } else {
+                pos.1 += 1;
+            }
+        }
+        pos
+    }
+}
+
+/// The spans of a parsed program, shaped like its instructions: one entry
+/// per instruction, holding the map of its body for ifs and loops. Programs
+/// loaded from `.bngc` files have an empty map.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap(pub Vec<(Span, SourceMap)>);
+
+impl SourceMap {
+    pub fn span(&self, idx: usize) -> Option<Span> {
+        self.0.get(idx).map(|(span, _)| *span)
+    }
+
+    /// The map of the body of the instruction at `idx`.
+    pub fn body(&self, idx: usize) -> &SourceMap {
+        const EMPTY: &SourceMap = &SourceMap(Vec::new());
+        self.0.get(idx).map_or(EMPTY, |(_, body)| body)
+    }
+
+    /// The span of the instruction at `path`, the index in each enclosing
+    /// body followed by its own.
+    pub fn at(&self, path: &[usize]) -> Option<Span> {
+        let (&idx, outer) = path.split_last()?;
+        outer.iter().fold(self, |map, &i| map.body(i)).span(idx)
+    }
+}
+
+/// How deep ifs and loops may nest, so a program can't overflow the stack
+/// of the parser, or of the `.bngc` reader.
+pub const MAX_DEPTH: usize = 1000;
+
+/// Like [`parse_bng_with`], but also returns where each instruction came from.
+pub fn parse_spanned(line: String, opts: &ParseOptions) -> Result<(Vec<Instr>, SourceMap), String> {
+    let chars = line.chars().collect::<Vec<char>>();
+    let end = chars.len();
+    let (mut instructions, mut map) = parse_from(&chars, 0, 1, 0)?;
+
+    if opts.implicit_exit {
+        instructions.push(Instr::Exit);
+        map.0.push((Span { start: end, end }, SourceMap::default()));
+    }
+
+    Ok((instructions, map))
+}
+
+/// Parses `chars`, which start `base` chars into the whole source, on line
+/// `line`, inside `depth` ifs and loops.
+fn parse_from(
+    chars: &[char],
+    base: usize,
+    mut line: u32,
+    depth: usize,
+) -> Result<(Vec<Instr>, SourceMap), String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("line {line}; ifs and loops are nested more than {MAX_DEPTH} deep."));
+    }
+
     let mut instructions: Vec<Instr> = Vec::new();
-    let mut chars = line.chars().collect::<Vec<char>>();
+    let mut i = 0;
+
+    let mut map = SourceMap::default();
+    let mut body_map = SourceMap::default();
+    let mut start = base;
 
-    let mut pos = (1u32, 1u32);
+    loop {
+        // everything pushed since the last char we started at came from it
+        let here = base + i;
+        while map.0.len() < instructions.len() {
+            let span = Span { start, end: here };
+            map.0.push((span, std::mem::take(&mut body_map)));
+        }
+        start = here;
 
-    while chars.len() >= 1 {
-        pos.1 += 1;
-        let c = chars.remove(0);
+        let Some(&c) = chars.get(i) else {
+            break;
+        };
+        i += 1;
 
         match c {
             '\n' => {
-                pos.0 += 1;
+                line += 1;
                 continue;
             }
             PUSH_DELIM | ' ' => continue,
             '0'..='9' => {
-                let mut nums = String::from(c);
-
-                let ch_clone = chars.clone();
-
-                if ch_clone.len() >= 1 {
-                    if !ch_clone.get(0).unwrap().is_numeric() {
-                        instructions.push(Instr::PushNum((c as i32 - 48) as f32));
-                        continue;
-                    }
-
-                    for x in chars.clone() {
-                        if x.is_numeric() {
-                            nums.push(x);
-                            chars.remove(0);
-                            pos.1 += 1;
-                        } else {
-                            break;
-                        }
-                    }
-                }
+                let digits = chars[i..].iter().take_while(|c| c.is_numeric()).count();
+                let nums: String = chars[i - 1..i + digits].iter().collect();
+                i += digits;
 
                 instructions.push(Instr::PushNum(nums.parse().unwrap()));
             }
             STR_DELIM => {
-                let mut tot_str = String::new();
-
-                let mut sc = chars.remove(0);
-
-                if sc == STR_DELIM {
-                    instructions.push(Instr::PushStr("".into()));
-                    continue;
-                } else {
-                    tot_str.push(sc);
-                }
-
-                while sc != STR_DELIM {
-                    if chars.len() == 0 {
-                        return Err(format!("line {}; never closed.", pos.0));
-                    }
-                    sc = chars.remove(0);
-
-                    if sc == '\n' {
-                        pos.0 += 1;
-                    } else {
-                        pos.1 += 1;
-                    }
-
-                    tot_str.push(sc);
-                }
-
-                let new = tot_str.strip_suffix(STR_DELIM);
-
-                if new.is_some() {
-                    tot_str = new.unwrap().into();
-                }
+                let Some(len) = chars[i..].iter().position(|&c| c == STR_DELIM) else {
+                    return Err(format!("line {line}; never closed."));
+                };
+                let tot_str: String = chars[i..i + len].iter().collect();
+                line += newlines(&chars[i..i + len]);
+                i += len + 1;
 
                 instructions.push(Instr::PushStr(tot_str));
                 continue;
@@ -83,13 +145,13 @@ pub fn parse_bng(line: String) -> Result<Vec<Instr>, String> {
             'c' => instructions.push(Instr::ClearStack),
             'C' => instructions.push(Instr::ClearScreen),
             'd' => instructions.push(Instr::Pop),
-            'D' => {},
-            'e' => {},
-            'E' => {},
+            'D' => instructions.push(Instr::AddTime),
+            'e' => instructions.push(Instr::Epoch),
+            'E' => instructions.push(Instr::EpochMillis),
             'f' => {},
-            'F' => {},
+            'F' => instructions.push(Instr::FormatTime),
             'g' => {},
-            'G' => {},
+            'G' => instructions.push(Instr::TimeDiff),
             'h' => {},
             'H' => {},
             'i' => instructions.push(Instr::Read),
@@ -120,13 +182,13 @@ pub fn parse_bng(line: String) -> Result<Vec<Instr>, String> {
             'U' => {},
             'v' => {},
             'V' => {},
-            'w' => {},
-            'W' => {},
+            'w' => instructions.push(Instr::Now),
+            'W' => instructions.push(Instr::ParseTime),
             'x' => {},
             'X' => {},
             'y' => {},
             'Y' => {},
-            'z' => {},
+            'z' => instructions.push(Instr::InZone),
             'Z' => {}
 
             PLUS => instructions.push(Instr::Plus),
@@ -144,40 +206,86 @@ pub fn parse_bng(line: String) -> Result<Vec<Instr>, String> {
             NOT => instructions.push(Instr::Not),
             IF => {
                 // 1 1 = ? ['1 == '1P]
-                let mut next = chars.remove(0);
-
-                while next.is_whitespace() {
-                    next = chars.remove(0);
-                }
+                let Some(skip) = chars[i..].iter().position(|c| !c.is_whitespace()) else {
+                    return Err(format!("line {line}; expected '[' after '{IF}'."));
+                };
+                line += newlines(&chars[i..i + skip]);
+                let next = chars[i + skip];
+                i += skip + 1;
 
                 if next != '[' {
                     return Err(format!("unexpected token after '{IF}': expected '[', but got '{next}'."))
                 }
 
-                let mut body = String::new();
-
-
-                while next != ']' {
-                    next = chars.remove(0);
-                    body.push(next);
-                }
-                body.pop().unwrap();
-                let mut parsed_body = match parse_bng(body) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e)
-                };
-                parsed_body.pop();
-
-                instructions.push(Instr::IfStmt(parsed_body));
+                let (body, map) = parse_body(chars, &mut i, '[', ']', base, &mut line, depth)?;
+                body_map = map;
+                instructions.push(Instr::IfStmt(body));
             },
+            LOOP_IN => {
+                // 3 ('hi'P)
+                let (body, map) = parse_body(chars, &mut i, LOOP_IN, LOOP_OUT, base, &mut line, depth)?;
+                body_map = map;
+                instructions.push(Instr::Loop(body));
+            }
             FN_DEF => instructions.push(Instr::FnDef),
-            FN_CALL => instructions.push(Instr::FnCall),
+            FN_CALL => {
+                let len = chars[i..].iter().take_while(|&&c| host::is_name_char(c)).count();
+                let name: String = chars[i..i + len].iter().collect();
+                i += len;
+                if name.is_empty() {
+                    return Err(format!("line {line}; expected a function name after '{FN_CALL}'."));
+                }
+                instructions.push(Instr::FnCall(name));
+            }
             
             _ => return Err(format!("unkown token: {}", c)),
         }
     }
 
-    instructions.push(Instr::Exit);
+    Ok((instructions, map))
+}
+
+/// Parses the body from `chars[*i]` up to the `close` matching an already
+/// consumed `open`, and moves `i` and `line` past it.
+fn parse_body(
+    chars: &[char],
+    i: &mut usize,
+    open: char,
+    close: char,
+    base: usize,
+    line: &mut u32,
+    depth: usize,
+) -> Result<(Vec<Instr>, SourceMap), String> {
+    let body = &chars[*i..];
+    let Some(end) = body_end(body, open, close) else {
+        return Err(format!("line {line}; '{open}' never closed."));
+    };
+    let parsed = parse_from(&body[..end], base + *i, *line, depth + 1)?;
+    *line += newlines(&body[..end]);
+    *i += end + 1;
+    Ok(parsed)
+}
+
+/// The index of the `close` matching an already consumed `open` in `chars`.
+fn body_end(chars: &[char], open: char, close: char) -> Option<usize> {
+    let mut depth = 1;
+    let mut in_str = false;
+
+    for (idx, &c) in chars.iter().enumerate() {
+        if c == STR_DELIM {
+            in_str = !in_str;
+        } else if !in_str && c == open {
+            depth += 1;
+        } else if !in_str && c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
 
-    Ok(instructions)
+fn newlines(chars: &[char]) -> u32 {
+    chars.iter().filter(|&&c| c == '\n').count() as u32
 }
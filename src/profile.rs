@@ -0,0 +1,242 @@
+//! Counts how often each instruction runs and how long it takes. Times are
+//! self times: an if or loop is only charged for itself, not its body. The
+//! tree walker's work between the instructions of a body, and some of the
+//! profiler's own, is charged to the if or loop around them.
+//!
+//! [`Profiler::folded`] writes the same numbers as folded stacks, one line
+//! per instruction with the ifs and loops around it, which flame graph tools
+//! like `flamegraph.pl` and `inferno` read:
+//!
+//! ```text
+//! Loop@1:3;Pop@1:6 1200
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+use crate::hook::{self, Hook};
+use crate::parse::SourceMap;
+use crate::*;
+
+/// How many locations the report lists.
+const TOP_LOCATIONS: usize = 20;
+
+#[derive(Debug)]
+pub struct Profiler {
+    src: String,
+    map: SourceMap,
+    /// Keyed by the path of the instruction.
+    stats: HashMap<Vec<usize>, Stat>,
+    /// The instructions that haven't finished yet, innermost last.
+    running: Vec<Running>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Stat {
+    name: &'static str,
+    count: u64,
+    time: Duration,
+}
+
+#[derive(Debug)]
+struct Running {
+    start: Instant,
+    /// Time spent in the instruction's body.
+    children: Duration,
+}
+
+impl Profiler {
+    /// A profiler for a program parsed from `src` with `map`.
+    pub fn new(src: &str, map: SourceMap) -> Self {
+        Self {
+            src: src.into(),
+            map,
+            stats: HashMap::new(),
+            running: Vec::new(),
+        }
+    }
+
+    fn finish(&mut self, inst: &Instr, path: &[usize]) {
+        let Some(run) = self.running.pop() else {
+            return;
+        };
+        let total = run.start.elapsed();
+
+        let stat = self.stats.entry(path.to_vec()).or_default();
+        stat.name = inst.name();
+        stat.count += 1;
+        stat.time += total.saturating_sub(run.children);
+
+        // the bookkeeping shouldn't count against the if or loop around it
+        if let Some(parent) = self.running.last_mut() {
+            parent.children += run.start.elapsed();
+        }
+    }
+
+    /// A table of counts and times by instruction kind and by location, the
+    /// slowest first.
+    pub fn report(&self) -> String {
+        let mut kinds: HashMap<&str, Stat> = HashMap::new();
+        for stat in self.stats.values() {
+            let kind = kinds.entry(stat.name).or_default();
+            kind.name = stat.name;
+            kind.count += stat.count;
+            kind.time += stat.time;
+        }
+        let mut kinds = kinds.into_values().collect::<Vec<_>>();
+        kinds.sort_by(|a, b| (b.time, b.count, a.name).cmp(&(a.time, a.count, b.name)));
+
+        let mut locations = self.stats.iter().collect::<Vec<_>>();
+        locations.sort_by(|(a_path, a), (b_path, b)| {
+            (b.time, b.count, a_path).cmp(&(a.time, a.count, b_path))
+        });
+
+        let count = kinds.iter().map(|k| k.count).sum::<u64>();
+        let time = kinds.iter().map(|k| k.time).sum::<Duration>();
+        let mut out = format!("{count} instructions in {time:.3?}\n\n");
+
+        writeln!(
+            out,
+            "{:>10}  {:>10}  {:>6}  instruction",
+            "count", "time", "%"
+        )
+        .unwrap();
+        for kind in &kinds {
+            writeln!(out, "{}  {}", row(kind, time), kind.name).unwrap();
+        }
+
+        writeln!(
+            out,
+            "\n{:>10}  {:>10}  {:>6}  location",
+            "count", "time", "%"
+        )
+        .unwrap();
+        for (path, stat) in locations.iter().take(TOP_LOCATIONS) {
+            writeln!(
+                out,
+                "{}  {} {}",
+                row(stat, time),
+                hook::location(&self.map, &self.src, path),
+                stat.name
+            )
+            .unwrap();
+        }
+        if locations.len() > TOP_LOCATIONS {
+            writeln!(out, "and {} more.", locations.len() - TOP_LOCATIONS).unwrap();
+        }
+        out
+    }
+
+    /// The times in nanoseconds as folded stacks.
+    pub fn folded(&self) -> String {
+        let mut paths = self.stats.keys().collect::<Vec<_>>();
+        paths.sort();
+
+        let mut out = String::new();
+        for path in paths {
+            let frames = (1..=path.len()).map(|len| {
+                let name = self.stats.get(&path[..len]).map_or("?", |s| s.name);
+                format!("{name}@{}", hook::location(&self.map, &self.src, &path[..len]))
+            });
+            let frames = frames.collect::<Vec<_>>().join(";");
+            writeln!(out, "{frames} {}", self.stats[path].time.as_nanos()).unwrap();
+        }
+        out
+    }
+}
+
+/// The count, time and share of the total time of `stat`.
+fn row(stat: &Stat, total: Duration) -> String {
+    let share = match total.is_zero() {
+        true => 0.0,
+        false => stat.time.as_secs_f64() / total.as_secs_f64() * 100.0,
+    };
+    let time = format!("{:.3?}", stat.time);
+    format!("{:>10}  {time:>10}  {share:>5.1}%", stat.count)
+}
+
+impl<R: BufRead, W: Write> Hook<R, W> for Profiler {
+    fn before(
+        &mut self,
+        _: &mut Interpreter<R, W>,
+        _: &Instr,
+        _: &[usize],
+    ) -> Result<ExecOutcome, String> {
+        self.running.push(Running {
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+        Ok(ExecOutcome::Done)
+    }
+
+    fn after(
+        &mut self,
+        _: &mut Interpreter<R, W>,
+        inst: &Instr,
+        path: &[usize],
+    ) -> Result<(), String> {
+        self.finish(inst, path);
+        Ok(())
+    }
+
+    fn error(&mut self, _: &mut Interpreter<R, W>, inst: &Instr, path: &[usize], _: &str) {
+        self.finish(inst, path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_and_folds() {
+        let src = "3 (1 d)\n1 1 = ? [2 P]";
+        let (instructions, map) = parse_spanned(src.into(), &ParseOptions::default()).unwrap();
+        let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+        let mut profiler = Profiler::new(src, map);
+        interpreter
+            .execute_hooked(&instructions, &mut profiler)
+            .unwrap();
+
+        let report = profiler.report();
+        assert!(report.starts_with("14 instructions in "));
+        // count and name of each row, the times vary between runs
+        let rows = report
+            .lines()
+            .filter_map(|l| Some((l.split_whitespace().next()?, l.split_whitespace().last()?)))
+            .collect::<Vec<_>>();
+        for row in [
+            ("7", "PushNum"),
+            ("3", "Pop"),
+            ("1", "Loop"),
+            ("1", "IfStmt"),
+            ("1", "PrintLn"),
+        ] {
+            assert!(rows.contains(&row), "{row:?} is missing from\n{report}");
+        }
+        assert!(report.contains(" 1:6 Pop\n"));
+
+        let folded = profiler.folded();
+        let stacks = folded
+            .lines()
+            .map(|l| l.rsplit_once(' ').unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stacks,
+            [
+                "PushNum@1:1",
+                "Loop@1:3",
+                "Loop@1:3;PushNum@1:4",
+                "Loop@1:3;Pop@1:6",
+                "PushNum@2:1",
+                "PushNum@2:3",
+                "Eq@2:5",
+                "IfStmt@2:7",
+                "IfStmt@2:7;PushNum@2:10",
+                "IfStmt@2:7;PrintLn@2:12",
+            ]
+        );
+    }
+}
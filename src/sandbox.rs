@@ -0,0 +1,145 @@
+//! Turns off what a program may do outside its stack and output. An
+//! instruction that needs a denied [`Capability`] fails with
+//! [`BngError::Permission`] instead of running.
+//!
+//! ```
+//! use bingbang::{sandbox::Capability, BngError, Interpreter};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.deny(Capability::Stdin);
+//! assert!(matches!(interpreter.run("i P"), Err(BngError::Permission(_))));
+//! ```
+
+use std::io::{BufRead, Write};
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Capability {
+    /// Ending the process with `q` or syscall 0.
+    Exit,
+    /// Reading the time with `t`, `T`, `w`, `e` and `E`, and its offset
+    /// with `'local' z`. Without it `W` reads times without an offset as
+    /// UTC.
+    Clock,
+    /// Reading input with `i`.
+    Stdin,
+    /// Clearing the screen with `C`.
+    Terminal,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::Exit,
+        Capability::Clock,
+        Capability::Stdin,
+        Capability::Terminal,
+    ];
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Exit => "exit",
+            Capability::Clock => "clock",
+            Capability::Stdin => "stdin",
+            Capability::Terminal => "terminal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    fn action(self) -> &'static str {
+        match self {
+            Capability::Exit => "exiting the process",
+            Capability::Clock => "reading the clock",
+            Capability::Stdin => "reading stdin",
+            Capability::Terminal => "controlling the terminal",
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    /// Makes instructions that need `capability` fail.
+    pub fn deny(&mut self, capability: Capability) {
+        if !self.denied.contains(&capability) {
+            self.denied.push(capability);
+        }
+    }
+
+    pub fn allow(&mut self, capability: Capability) {
+        self.denied.retain(|&c| c != capability);
+    }
+
+    /// Denies every capability, including ones added in later versions.
+    pub fn sandbox(&mut self) {
+        for capability in Capability::ALL {
+            self.deny(capability);
+        }
+    }
+
+    pub fn is_allowed(&self, capability: Capability) -> bool {
+        !self.denied.contains(&capability)
+    }
+
+    /// Fails unless the instruction at `idx` may use `capability`.
+    pub(crate) fn need(
+        &mut self,
+        capability: Capability,
+        inst: &Instr,
+        idx: usize,
+    ) -> Result<(), String> {
+        if self.is_allowed(capability) {
+            return Ok(());
+        }
+        self.usage.failure = Some(BngError::Permission);
+        Err(format!(
+            "while performing [{}] at index {idx}, {} is not allowed.",
+            inst.name(),
+            capability.action()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn denies_capabilities() {
+        let mut interpreter = Interpreter::with_io(&b"line\n"[..], Vec::new());
+        interpreter.sandbox();
+        for src in ["q", "1 0 $", "t", "'%Y' T", "w", "e", "E", "'2024-02-29T12:00:00Z' 'local' z", "i", "C"] {
+            assert!(
+                matches!(interpreter.run(src), Err(BngError::Permission(_))),
+                "`{src}` ran in the sandbox"
+            );
+        }
+        assert_eq!(
+            interpreter.run("1 P i"),
+            Err(BngError::Permission(
+                "while performing [Read] at index 2, reading stdin is not allowed.".into()
+            ))
+        );
+        // the other syscalls fail like they always do
+        assert!(matches!(
+            interpreter.run("1 7 $"),
+            Err(BngError::Runtime(_))
+        ));
+        assert!(matches!(
+            interpreter.run("'a' +"),
+            Err(BngError::Runtime(_))
+        ));
+
+        // without the clock's offset, times are read in utc
+        interpreter.set_clock(clock::Clock::parse_fixed("2024-02-29T12:00:00+05:00").unwrap());
+        assert_eq!(interpreter.run("'2024-02-29 10:00' '%Y-%m-%d %H:%M' W P"), Ok(ExecOutcome::Done));
+
+        interpreter.allow(Capability::Stdin);
+        assert_eq!(interpreter.run("i P"), Ok(ExecOutcome::Done));
+        assert!(!interpreter.is_allowed(Capability::Exit));
+        assert_eq!(interpreter.into_io().1, b"1\n2024-02-29T10:00:00+00:00\nline\n");
+    }
+}
@@ -1,5 +1,8 @@
 
-#[derive(Debug)]
+/// The stack a program runs on.
+pub type Stack = Vec<StackVal>;
+
+#[derive(Debug, Clone)]
 pub enum StackVal {
     Number(f32),
     String(String),
@@ -21,7 +24,7 @@ impl std::cmp::PartialEq for StackVal {
                 match other {
                     Self::Number(n2) => ((*b as i8) as f32) == *n2,
                     Self::Bool(b2) => b == b2,
-                    Self::String(s) => b.to_string() == s.to_string()
+                    Self::String(s) => b.to_string() == *s
                 }
             },
             Self::String(s) => {
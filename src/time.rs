@@ -0,0 +1,356 @@
+//! Dates and times beyond `t` and `T`. A timestamp is an RFC 3339 string
+//! like `2024-02-29T12:00:00+01:00`, and a duration is a Number of seconds.
+//! Unix times are pushed as strings of digits, since a Number (an f32) is
+//! off by minutes at today's values.
+//!
+//! ```text
+//! w   push the time now, in the clock's offset
+//! e   push the Unix time now in seconds
+//! E   push the Unix time now in milliseconds
+//! W   pop a format, then a string, and push the timestamp the string holds
+//! F   pop a format, then a timestamp, and push the formatted timestamp
+//! D   pop seconds, then a timestamp, and push the timestamp that much later
+//! G   pop a timestamp, then another, and push the seconds from the second
+//!     to the first
+//! z   pop a zone, then a timestamp, and push the same time in that zone:
+//!     `utc`, `local` (the clock's offset) or an offset like `+05:30`
+//! ```
+//!
+//! Strings parsed with `W` without an offset are in the clock's offset, or
+//! in UTC when reading the clock isn't allowed; ones without a time of day
+//! are at midnight. `'local' z` needs to read the clock. `%s` parses Unix seconds.
+//! Formats given to `T`, `F` and `W` are checked first, and one with a
+//! specifier chrono doesn't know fails the run.
+//!
+//! ```
+//! use bingbang::{clock::Clock, Interpreter};
+//!
+//! let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+//! interpreter.set_clock(Clock::parse_fixed("2024-02-29T12:00:00+01:00").unwrap());
+//! interpreter.run("t '%d-%m-%Y' W 90 D '+00:00' z P").unwrap();
+//! assert_eq!(interpreter.output(), b"2024-02-28T23:01:30+00:00\n");
+//! ```
+
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use chrono::format::{parse, Item, Parsed, StrftimeItems};
+use chrono::{DateTime, Duration, FixedOffset, TimeZone};
+
+use crate::sandbox::Capability;
+use crate::*;
+
+/// Reads an RFC 3339 timestamp.
+pub fn parse_timestamp(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(s).map_err(|e| format!("`{s}` is not a timestamp ({e})"))
+}
+
+/// Fails if chrono doesn't know a specifier in `format`, with the format
+/// shown and the first unknown specifier underlined.
+pub fn check_format(format: &str) -> Result<(), String> {
+    let invalid = |f: &str| StrftimeItems::new(f).any(|item| matches!(item, Item::Error));
+    if !invalid(format) {
+        return Ok(());
+    }
+
+    let (start, end) = specifiers(format)
+        .into_iter()
+        .find(|&(start, end)| invalid(&format[start..end]))
+        .unwrap_or((format.rfind('%').unwrap_or(0), format.len()));
+    Err(underline(format, start, end, "is not a valid time specifier"))
+}
+
+/// Like [`check_format`], also failing on specifiers that C99's `strftime`
+/// doesn't know or, like `%Z`, formats differently from chrono. Compiled
+/// programs pass their formats to `strftime`.
+pub fn check_c_format(format: &str) -> Result<(), String> {
+    check_format(format)?;
+    match specifiers(format).into_iter().find(|&(start, end)| {
+        !matches!(&format[start + 1..end], "a" | "A" | "b" | "B" | "c" | "C" | "d" | "D" | "e"
+            | "F" | "g" | "G" | "h" | "H" | "I" | "j" | "m" | "M" | "n" | "p" | "r" | "R"
+            | "S" | "t" | "T" | "u" | "U" | "V" | "w" | "W" | "x" | "X" | "y" | "Y" | "z")
+    }) {
+        Some((start, end)) => Err(underline(format, start, end, "is not a C99 strftime specifier")),
+        None => Ok(()),
+    }
+}
+
+/// `format` with the specifier at `start..end` underlined.
+fn underline(format: &str, start: usize, end: usize, what: &str) -> String {
+    let col = format[..start].chars().count();
+    let width = format[start..end].chars().count().max(1);
+    format!(
+        "`{}` {what}.\n    {format}\n    {}{}",
+        &format[start..end],
+        " ".repeat(col),
+        "^".repeat(width)
+    )
+}
+
+/// The byte ranges of the specifiers in `format`, like `%d`, `%-d` or
+/// `%.3f`, leaving out `%%`.
+fn specifiers(format: &str) -> Vec<(usize, usize)> {
+    let bytes = format.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        if bytes.get(i) == Some(&b'%') {
+            i += 1;
+            continue;
+        }
+        if matches!(bytes.get(i), Some(b'-' | b'_' | b'0')) {
+            i += 1;
+        }
+        while matches!(bytes.get(i), Some(b':' | b'.' | b'#' | b'0'..=b'9')) {
+            i += 1;
+        }
+        if let Some(c) = format[i..].chars().next() {
+            i += c.len_utf8();
+        }
+        found.push((start, i));
+    }
+    found
+}
+
+/// Formats `time`, failing instead of panicking on a bad `format`. Run
+/// [`check_format`] first for an error that says what is wrong with it.
+pub fn format(time: &DateTime<FixedOffset>, format: &str) -> Result<String, String> {
+    let mut out = String::new();
+    write!(out, "{}", time.format(format)).map_err(|_| format!("can't format with `{format}`."))?;
+    Ok(out)
+}
+
+/// Reads `s` in `format`, taking `zone` when `s` has no offset.
+pub fn parse_with(
+    s: &str,
+    format: &str,
+    zone: FixedOffset,
+) -> Result<DateTime<FixedOffset>, String> {
+    let fail = |e| format!("`{s}` doesn't match `{format}` ({e})");
+
+    let mut parsed = Parsed::new();
+    parse(&mut parsed, s, StrftimeItems::new(format)).map_err(fail)?;
+    if parsed.timestamp.is_none() && parsed.hour_div_12.is_none() {
+        parsed.set_hour(0).map_err(fail)?;
+        parsed.set_minute(0).map_err(fail)?;
+    }
+
+    let zone = match parsed.offset {
+        Some(secs) => FixedOffset::east_opt(secs).ok_or(format!("`{s}` has an invalid offset"))?,
+        None => zone,
+    };
+    let local = parsed
+        .to_naive_datetime_with_offset(zone.local_minus_utc())
+        .map_err(fail)?;
+    zone.from_local_datetime(&local)
+        .single()
+        .ok_or(format!("`{s}` is out of range"))
+}
+
+/// The offset called `name`: `utc`, `local` for `local`, or `+HH:MM`,
+/// `+HHMM` or `+HH` with either sign.
+pub fn zone(name: &str, local: FixedOffset) -> Result<FixedOffset, String> {
+    let unknown = || format!("unknown zone `{name}`, expected utc, local or an offset like +05:30");
+
+    match name {
+        "utc" | "UTC" | "Z" => return Ok(FixedOffset::east_opt(0).unwrap()),
+        "local" => return Ok(local),
+        _ => {}
+    }
+
+    let (sign, rest) = if let Some(rest) = name.strip_prefix('+') {
+        (1, rest)
+    } else if let Some(rest) = name.strip_prefix('-') {
+        (-1, rest)
+    } else {
+        return Err(unknown());
+    };
+    let digits = rest.replace(':', "");
+    if !matches!(digits.len(), 2 | 4) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(unknown());
+    }
+    let hours: i32 = digits[..2].parse().unwrap();
+    let minutes: i32 = digits[2..].parse().unwrap_or(0);
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(unknown)
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    /// Performs one of the instructions in this module's table.
+    pub(crate) fn time_step(&mut self, inst: &Instr, idx: usize) -> Result<(), String> {
+        let fail = |e: String| format!("while performing [{inst:?}] at index {idx}, {e}.");
+        let bad_format = |e: String| format!("while performing [{inst:?}] at index {idx}, {e}");
+
+        let val = match inst {
+            Instr::Now => {
+                self.need(Capability::Clock, inst, idx)?;
+                self.clock.now().to_rfc3339()
+            }
+            Instr::Epoch => {
+                self.need(Capability::Clock, inst, idx)?;
+                self.clock.now().timestamp().to_string()
+            }
+            Instr::EpochMillis => {
+                self.need(Capability::Clock, inst, idx)?;
+                self.clock.now().timestamp_millis().to_string()
+            }
+            Instr::ParseTime => {
+                let format = self.pop_string(inst, idx, "format")?;
+                check_format(&format).map_err(bad_format)?;
+                let s = self.pop_string(inst, idx, "string to parse")?;
+                // the clock's offset says where the clock is, so without it
+                // strings are read as utc
+                let local = match self.is_allowed(Capability::Clock) {
+                    true => *self.clock.now().offset(),
+                    false => FixedOffset::east_opt(0).unwrap(),
+                };
+                parse_with(&s, &format, local).map_err(fail)?.to_rfc3339()
+            }
+            Instr::FormatTime => {
+                let format = self.pop_string(inst, idx, "format")?;
+                check_format(&format).map_err(bad_format)?;
+                let time = self.pop_time(inst, idx)?;
+                self::format(&time, &format).map_err(bad_format)?
+            }
+            Instr::AddTime => {
+                let secs = match self.stack.pop() {
+                    Some(StackVal::Number(n)) => n,
+                    Some(v) => {
+                        return Err(format!(
+                            "while trying to perform [{inst:?}] at index {idx}, expected a Number of seconds, but got {v:?}."
+                        ))
+                    }
+                    None => return Err(fail("stack is empty".into())),
+                };
+                let time = self.pop_time(inst, idx)?;
+                let nanos = secs as f64 * 1e9;
+                let later = match nanos.abs() < i64::MAX as f64 {
+                    true => time.checked_add_signed(Duration::nanoseconds(nanos as i64)),
+                    false => None,
+                };
+                later
+                    .ok_or_else(|| fail(format!("{time} plus {secs} seconds is out of range")))?
+                    .to_rfc3339()
+            }
+            Instr::TimeDiff => {
+                let a = self.pop_time(inst, idx)?;
+                let b = self.pop_time(inst, idx)?;
+                let millis = (a - b).num_milliseconds();
+                self.stack.push(StackVal::Number(millis as f32 / 1000.0));
+                return Ok(());
+            }
+            Instr::InZone => {
+                let name = self.pop_string(inst, idx, "zone")?;
+                let time = self.pop_time(inst, idx)?;
+                if name == "local" {
+                    self.need(Capability::Clock, inst, idx)?;
+                }
+                let zone = zone(&name, *self.clock.now().offset()).map_err(fail)?;
+                time.with_timezone(&zone).to_rfc3339()
+            }
+            _ => unreachable!("{inst:?} is not a time instruction"),
+        };
+        self.stack.push(StackVal::String(val));
+        Ok(())
+    }
+
+    fn pop_string(&mut self, inst: &Instr, idx: usize, what: &str) -> Result<String, String> {
+        match self.stack.pop() {
+            Some(StackVal::String(s)) => Ok(s),
+            Some(v) => Err(format!(
+                "while trying to perform [{inst:?}] at index {idx}, expected a String {what}, but got {v:?}."
+            )),
+            None => Err(format!(
+                "while performing [{inst:?}] at index {idx}, stack is empty."
+            )),
+        }
+    }
+
+    fn pop_time(&mut self, inst: &Instr, idx: usize) -> Result<DateTime<FixedOffset>, String> {
+        let s = self.pop_string(inst, idx, "timestamp")?;
+        parse_timestamp(&s).map_err(|e| format!("while performing [{inst:?}] at index {idx}, {e}."))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::Clock;
+
+    fn run(src: &str) -> Result<String, BngError> {
+        let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+        interpreter.set_clock(Clock::parse_fixed("2024-02-29T12:00:00.25+01:00").unwrap());
+        interpreter.run(src)?;
+        Ok(String::from_utf8(interpreter.into_io().1).unwrap())
+    }
+
+    #[test]
+    fn dates_and_times() {
+        assert_eq!(
+            run("w P e P E P").unwrap(),
+            "2024-02-29T12:00:00.250+01:00\n1709204400\n1709204400250\n"
+        );
+        assert_eq!(
+            run("'1.3.2024 08:30' '%d.%m.%Y %H:%M' W P '5 Mar 2024 +0200' '%d %b %Y %z' W P")
+                .unwrap(),
+            "2024-03-01T08:30:00+01:00\n2024-03-05T00:00:00+02:00\n"
+        );
+        assert_eq!(
+            run("'86400' '%s' W '%A %H:%M %:z' F P").unwrap(),
+            "Friday 01:00 +01:00\n"
+        );
+        assert_eq!(
+            run("w 3600 D 'utc' z P w 5 0 - D '-0330' z P").unwrap(),
+            "2024-02-29T12:00:00.250+00:00\n2024-02-29T07:29:55.250-03:30\n"
+        );
+        assert_eq!(run("'2024-01-01T00:00:00Z' w G P").unwrap(), "5137200\n");
+        assert_eq!(run("w '2024-02-29T10:00:00Z' G P").unwrap(), "-3600.25\n");
+
+        for (src, e) in [
+            ("'noon' '%H:%M' W", "`noon` doesn't match `%H:%M`"),
+            ("'today' 'utc' z", "`today` is not a timestamp"),
+            ("w 'mars' z", "unknown zone `mars`"),
+            ("w '+25' z", "unknown zone `+25`"),
+            ("w 'é' z", "unknown zone `é`"),
+            ("w '+é0' z", "unknown zone `+é0`"),
+            ("w 1 F", "expected a String format, but got Number(1.0)"),
+        ] {
+            let got = run(src).unwrap_err().to_string();
+            assert!(got.contains(e), "`{src}` failed with `{got}`");
+        }
+    }
+
+    #[test]
+    fn checks_formats() {
+        assert_eq!(
+            run("'%Y-%Q-%d' T").unwrap_err(),
+            BngError::Runtime(
+                "while performing [TimeFmt] at index 1, `%Q` is not a valid time specifier.\n    \
+                 %Y-%Q-%d\n       ^^"
+                    .into()
+            )
+        );
+        let e = run("w 'ü %Ek' F").unwrap_err().to_string();
+        assert!(
+            e.ends_with("`%E` is not a valid time specifier.\n    ü %Ek\n      ^^"),
+            "{e}"
+        );
+        assert!(run("'x' '100%' W")
+            .unwrap_err()
+            .to_string()
+            .ends_with("100%\n       ^"));
+
+        assert_eq!(check_c_format("%F %T %z %%"), Ok(()));
+        assert_eq!(
+            check_c_format("%d %-m"),
+            Err("`%-m` is not a C99 strftime specifier.\n    %d %-m\n       ^^^".into())
+        );
+        assert!(check_c_format("%Z").is_err());
+        assert!(check_c_format("%Q").unwrap_err().contains("not a valid time specifier"));
+    }
+}
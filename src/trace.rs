@@ -0,0 +1,200 @@
+//! Logs every instruction a program performs with the stack before and after
+//! it, as text for reading or as JSON lines for tools. An if or loop is
+//! logged after its body, with the stack the body left.
+//!
+//! ```text
+//! 1:1     PushNum(2.0)  [] -> [Number(2.0)]
+//! ```
+//!
+//! ```text
+//! {"path":[0],"line":1,"col":1,"inst":"PushNum(2.0)","before":[],"after":[2]}
+//! ```
+//!
+//! An instruction that fails has `error` instead of `after`. `line` and
+//! `col` are left out for programs without a source.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use crate::hook::{self, Hook};
+use crate::parse::SourceMap;
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct Tracer<T> {
+    out: T,
+    format: Format,
+    src: String,
+    map: SourceMap,
+    /// The stacks before the instructions that haven't finished yet: the
+    /// current one and the ifs and loops around it.
+    before: Vec<Vec<StackVal>>,
+}
+
+impl<T: Write> Tracer<T> {
+    /// A tracer for a program parsed from `src` with `map`, writing to `out`.
+    pub fn new(out: T, format: Format, src: &str, map: SourceMap) -> Self {
+        Self {
+            out,
+            format,
+            src: src.into(),
+            map,
+            before: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.out
+    }
+
+    fn log(
+        &mut self,
+        inst: &Instr,
+        path: &[usize],
+        after: Result<&[StackVal], &str>,
+    ) -> Result<(), String> {
+        let before = self.before.pop().unwrap_or_default();
+        let mut line = String::new();
+        match self.format {
+            Format::Text => {
+                let pos = hook::location(&self.map, &self.src, path);
+                write!(line, "{pos:<8}").unwrap();
+                let indent = "  ".repeat(path.len() - 1);
+                write!(line, "{indent}{}  {before:?} -> ", inst.brief()).unwrap();
+                match after {
+                    Ok(after) => writeln!(line, "{after:?}"),
+                    Err(e) => writeln!(line, "error: {e}"),
+                }
+                .unwrap();
+            }
+            Format::Json => {
+                let indices = path.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+                write!(line, "{{\"path\":[{}]", indices.join(",")).unwrap();
+                if let Some((l, c)) = self.map.at(path).map(|span| span.line_col(&self.src)) {
+                    write!(line, ",\"line\":{l},\"col\":{c}").unwrap();
+                }
+                write!(
+                    line,
+                    ",\"inst\":{},\"before\":{}",
+                    json_str(&inst.brief()),
+                    json_stack(&before)
+                )
+                .unwrap();
+                match after {
+                    Ok(after) => writeln!(line, ",\"after\":{}}}", json_stack(after)),
+                    Err(e) => writeln!(line, ",\"error\":{}}}", json_str(e)),
+                }
+                .unwrap();
+            }
+        }
+
+        self.out
+            .write_all(line.as_bytes())
+            .map_err(|e| format!("failed to write the trace: {e}"))
+    }
+}
+
+impl<R: BufRead, W: Write, T: Write> Hook<R, W> for Tracer<T> {
+    fn before(
+        &mut self,
+        interpreter: &mut Interpreter<R, W>,
+        _: &Instr,
+        _: &[usize],
+    ) -> Result<ExecOutcome, String> {
+        self.before.push(interpreter.stack().to_vec());
+        Ok(ExecOutcome::Done)
+    }
+
+    fn after(
+        &mut self,
+        interpreter: &mut Interpreter<R, W>,
+        inst: &Instr,
+        path: &[usize],
+    ) -> Result<(), String> {
+        self.log(inst, path, Ok(interpreter.stack()))
+    }
+
+    fn error(&mut self, _: &mut Interpreter<R, W>, inst: &Instr, path: &[usize], error: &str) {
+        // the run already failed, losing the last line of its trace too
+        // wouldn't help anyone
+        let _ = self.log(inst, path, Err(error));
+        let _ = self.out.flush();
+    }
+}
+
+fn json_stack(stack: &[StackVal]) -> String {
+    let vals = stack.iter().map(|v| match v {
+        StackVal::Number(n) if n.is_finite() => n.to_string(),
+        // JSON has no infinities or NaN
+        StackVal::Number(n) => json_str(&n.to_string()),
+        StackVal::String(s) => json_str(s),
+        StackVal::Bool(b) => b.to_string(),
+    });
+    format!("[{}]", vals.collect::<Vec<_>>().join(","))
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trace(src: &str, format: Format) -> (Result<ExecOutcome, BngError>, String) {
+        let (instructions, map) = parse_spanned(src.into(), &ParseOptions::default()).unwrap();
+        let mut interpreter = Interpreter::with_io(&b""[..], Vec::new());
+        let mut tracer = Tracer::new(Vec::new(), format, src, map);
+        let result = interpreter.execute_hooked(&instructions, &mut tracer);
+        (result, String::from_utf8(tracer.into_inner()).unwrap())
+    }
+
+    #[test]
+    fn traces_bodies() {
+        let (result, out) = trace("1 1 =\n? [2 (d)]", Format::Text);
+        assert!(result.is_ok());
+        assert_eq!(
+            out,
+            "\
+1:1     PushNum(1.0)  [] -> [Number(1.0)]
+1:3     PushNum(1.0)  [Number(1.0)] -> [Number(1.0), Number(1.0)]
+1:5     Eq  [Number(1.0), Number(1.0)] -> [Bool(true)]
+2:4       PushNum(2.0)  [] -> [Number(2.0)]
+2:7         Pop  [] -> []
+2:7         Pop  [] -> []
+2:6       Loop  [Number(2.0)] -> []
+2:1     IfStmt  [Bool(true)] -> []
+"
+        );
+
+        let (result, out) = trace("'a\"' 1 T", Format::Json);
+        assert!(result.is_err());
+        assert_eq!(
+            out,
+            "\
+{\"path\":[0],\"line\":1,\"col\":1,\"inst\":\"PushStr(\\\"a\\\\\\\"\\\")\",\"before\":[],\"after\":[\"a\\\"\"]}
+{\"path\":[1],\"line\":1,\"col\":6,\"inst\":\"PushNum(1.0)\",\"before\":[\"a\\\"\"],\"after\":[\"a\\\"\",1]}
+{\"path\":[2],\"line\":1,\"col\":8,\"inst\":\"TimeFmt\",\"before\":[\"a\\\"\",1],\"error\":\"while trying to perform [TimeFmt] at index 2, expected type String, but got Number(1.0).\"}
+"
+        );
+    }
+}
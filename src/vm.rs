@@ -0,0 +1,149 @@
+//! Runs a [`Chunk`] in a single dispatch loop. The common stack and
+//! arithmetic ops are handled here directly; everything else, including
+//! every error message, goes through the tree walker's `step`.
+
+use std::io::{BufRead, Write};
+
+use crate::bytecode::{Chunk, Op};
+use crate::*;
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    /// Runs a compiled chunk on the current stack.
+    pub fn execute_chunk(&mut self, chunk: &Chunk) -> Result<ExecOutcome, BngError> {
+        self.start_run();
+        self.run_chunk(chunk).map_err(|e| self.run_error(e))
+    }
+
+    fn run_chunk(&mut self, chunk: &Chunk) -> Result<ExecOutcome, String> {
+        let ops = &chunk.ops;
+        let mut loops: Vec<i64> = Vec::new();
+        let mut pc = 0;
+        let limited = self.limited();
+
+        while pc < ops.len() {
+            let op = &ops[pc];
+            pc += 1;
+            if limited {
+                self.tick()?;
+            }
+
+            match op {
+                Op::PushNum(n) => self.stack.push(StackVal::Number(*n)),
+                Op::PushStr(id) => self
+                    .stack
+                    .push(StackVal::String(chunk.strings[*id as usize].clone())),
+
+                Op::Plus | Op::Sub | Op::Mul | Op::Div => {
+                    let len = self.stack.len();
+                    match self.stack.get(len.wrapping_sub(2)..) {
+                        Some([StackVal::Number(b), StackVal::Number(a)]) => {
+                            let (a, b) = (*a, *b);
+                            self.stack.truncate(len - 2);
+                            self.stack.push(StackVal::Number(match op {
+                                Op::Plus => a + b,
+                                Op::Sub => a - b,
+                                Op::Mul => a * b,
+                                _ => a / b,
+                            }));
+                        }
+                        _ => self.slow_path(op, pc - 1)?,
+                    }
+                }
+                Op::GreaterThan | Op::LessThan => {
+                    let len = self.stack.len();
+                    match self.stack.get(len.wrapping_sub(2)..) {
+                        Some([StackVal::Number(b), StackVal::Number(a)]) => {
+                            let res = if *op == Op::GreaterThan { a > b } else { a < b };
+                            self.stack.truncate(len - 2);
+                            self.stack.push(StackVal::Bool(res));
+                        }
+                        _ => self.slow_path(op, pc - 1)?,
+                    }
+                }
+                Op::Eq => {
+                    if self.stack.len() < 2 {
+                        self.slow_path(op, pc - 1)?;
+                    } else {
+                        let a = self.stack.pop().unwrap();
+                        let b = self.stack.pop().unwrap();
+                        self.stack.push(StackVal::Bool(a == b));
+                    }
+                }
+                Op::Not => match self.stack.last_mut() {
+                    Some(StackVal::Bool(b)) => *b = !*b,
+                    _ => self.slow_path(op, pc - 1)?,
+                },
+                Op::Pop => {
+                    self.stack.pop();
+                }
+
+                Op::JumpUnless(target) => match self.stack.last() {
+                    Some(StackVal::Bool(b)) => {
+                        if *b {
+                            self.enter(pc - 1)?;
+                        } else {
+                            pc = *target as usize;
+                        }
+                        self.stack.pop();
+                    }
+                    _ => self.slow_path(op, pc - 1)?,
+                },
+                Op::LoopStart(target) => match self.stack.last() {
+                    Some(StackVal::Number(n)) => {
+                        let count = *n as i64;
+                        self.stack.pop();
+                        if count > 0 {
+                            self.enter(pc - 1)?;
+                            loops.push(count);
+                        } else {
+                            pc = *target as usize;
+                        }
+                    }
+                    _ => self.slow_path(op, pc - 1)?,
+                },
+                Op::IfEnd => self.leave(),
+                Op::LoopNext(target) => {
+                    let left = loops.last_mut().unwrap();
+                    *left -= 1;
+                    if *left > 0 {
+                        pc = *target as usize;
+                    } else {
+                        self.leave();
+                        loops.pop();
+                    }
+                }
+
+                Op::Instr(inst) => {
+                    if let ExecOutcome::Exit(code) = self.step(inst, pc - 1)? {
+                        return Ok(ExecOutcome::Exit(code));
+                    }
+                }
+            }
+            if limited {
+                self.check_stack(pc - 1)?;
+            }
+        }
+
+        Ok(ExecOutcome::Done)
+    }
+
+    /// Lets the tree walker handle an op whose operands are missing or of
+    /// the wrong type, which always ends in its error.
+    fn slow_path(&mut self, op: &Op, idx: usize) -> Result<(), String> {
+        let inst = match op {
+            Op::Plus => Instr::Plus,
+            Op::Sub => Instr::Sub,
+            Op::Mul => Instr::Mul,
+            Op::Div => Instr::Div,
+            Op::GreaterThan => Instr::GreaterThan,
+            Op::LessThan => Instr::LessThan,
+            Op::Eq => Instr::Eq,
+            Op::Not => Instr::Not,
+            Op::JumpUnless(_) => Instr::IfStmt(Vec::new()),
+            Op::LoopStart(_) => Instr::Loop(Vec::new()),
+            _ => unreachable!("{op:?} has no slow path"),
+        };
+        self.step(&inst, idx)?;
+        unreachable!("{inst:?} succeeded on the slow path")
+    }
+}